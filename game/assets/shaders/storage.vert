@@ -0,0 +1,9 @@
+#version 450
+
+layout(set = 0, binding = 0) readonly buffer Positions {
+    vec2 positions[];
+};
+
+void main() {
+    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
+}
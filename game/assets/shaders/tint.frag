@@ -0,0 +1,11 @@
+#version 450
+
+layout(push_constant) uniform Tint {
+    vec4 color;
+};
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = color;
+}
@@ -0,0 +1,16 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform Transform {
+    vec2 offset;
+    float scale;
+};
+
+vec2 positions[3] = vec2[](
+    vec2(0.0, -1.0),
+    vec2(1.0, 1.0),
+    vec2(-1.0, 1.0)
+);
+
+void main() {
+    gl_Position = vec4(positions[gl_VertexIndex] * scale + offset, 0.0, 1.0);
+}
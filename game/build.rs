@@ -5,6 +5,7 @@ use shaderc::{Compiler, ShaderKind};
 const SPV_EXT: &'static str = "spv";
 const VERT_EXT: &'static str = "vert";
 const FRAG_EXT: &'static str = "frag";
+const COMP_EXT: &'static str = "comp";
 
 fn main() {
     update_shaders();
@@ -39,31 +40,16 @@ fn compile_shaders(src: &Path, dst: &Path) {
 
     for path in fs::read_dir(src).expect("Failed to read dir") {
         if let Ok(entry) = path {
-            if entry.path().extension().is_some_and(|ext| ext == OsStr::new(VERT_EXT)) {
-                let src = format!("{}/{}", src.display(), entry.file_name().to_str().unwrap());
-                let dst = format!(
-                    "{}/{}.{}",
-                    dst.display(),
-                    entry.file_name().to_str().unwrap(),
-                    SPV_EXT
-                );
-                compile_and_write(&compiler, &Path::new(&src), Path::new(&dst), ShaderKind::Vertex);
-            }
-            if entry.path().extension().is_some_and(|ext| ext == OsStr::new(FRAG_EXT)) {
-                let src = format!("{}/{}", src.display(), entry.file_name().to_str().unwrap());
-                let dst = format!(
-                    "{}/{}.{}",
-                    dst.display(),
-                    entry.file_name().to_str().unwrap(),
-                    SPV_EXT
-                );
-                compile_and_write(
-                    &compiler,
-                    &Path::new(&src),
-                    &Path::new(&dst),
-                    ShaderKind::Fragment,
-                );
-            }
+            let kind = match entry.path().extension().and_then(OsStr::to_str) {
+                Some(VERT_EXT) => ShaderKind::Vertex,
+                Some(FRAG_EXT) => ShaderKind::Fragment,
+                Some(COMP_EXT) => ShaderKind::Compute,
+                _ => continue,
+            };
+            let src = format!("{}/{}", src.display(), entry.file_name().to_str().unwrap());
+            let dst =
+                format!("{}/{}.{}", dst.display(), entry.file_name().to_str().unwrap(), SPV_EXT);
+            compile_and_write(&compiler, Path::new(&src), Path::new(&dst), kind);
         }
     }
 }
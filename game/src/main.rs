@@ -1,26 +1,73 @@
-use platform::{utils, vulkan::*, window::*};
+use platform::{clock::*, utils, vulkan::*, window::*};
 
 fn main() {
     let mut window = Window::new("Лучшая игра".to_string(), 400, 400);
-    let mut vulkan_context = VulkanContext::new(400, 400, true);
+    let mut vulkan_context = VulkanContext::new(400, 400, DebugConfig::VALIDATION);
     let mut pipeline = None;
+    let mut clock = FrameClock::default();
 
     while window.exists {
         update_window(&mut window);
         update_context(&mut vulkan_context, &window);
         update_pass(&mut vulkan_context);
+        let screenshot_requested = window.input.was_pressed_this_frame(KeyCode::F12);
+        if window.input.was_pressed_this_frame(KeyCode::F11) {
+            for scope in gpu_history(&vulkan_context) {
+                utils::trace(format!("GPU {}", scope));
+            }
+            utils::trace(format!(
+                "CPU {:.1} fps, p99 {:?}, fence wait {:?}, work {:?}",
+                clock.fps,
+                frame_time_percentile(&clock, 99.0),
+                clock.fence_wait,
+                clock.cpu_time
+            ));
+        }
 
         if pipeline.is_none() {
             let pipeline_config = PipelineConfig {
                 vertext_shader_source: include_bytes!("../assets/compiled/test.vert.spv"),
                 fragment_shader_source: include_bytes!("../assets/compiled/test.frag.spv"),
+                vertex_layouts: &[],
+                state: PipelineState::default(),
             };
             pipeline = create_pipeline(&mut vulkan_context, pipeline_config).ok();
             utils::trace(format!("Pipeline created: {:?}", pipeline));
+            #[cfg(feature = "hot-reload")]
+            if let Some(pipeline) = pipeline {
+                watch_shaders(
+                    &mut vulkan_context,
+                    concat!(env!("CARGO_MANIFEST_DIR"), "/assets/shaders"),
+                );
+                watch_pipeline(&mut vulkan_context, pipeline, "test.vert", "test.frag");
+            }
         }
+        #[cfg(feature = "hot-reload")]
+        reload_shaders(&mut vulkan_context);
 
         begin_frame(&mut vulkan_context);
-        draw_pass(&mut vulkan_context);
+        begin_draw_pass(&mut vulkan_context);
+        if let Some(pipeline) = pipeline {
+            if let Err(e) = draw(&mut vulkan_context, pipeline, &[], &[], 3, 1) {
+                utils::error(format!("Draw failed: {:?}", e));
+            }
+        }
+        end_draw_pass(&mut vulkan_context);
+        if screenshot_requested {
+            if let Err(e) = capture_frame(&mut vulkan_context) {
+                utils::error(format!("Screenshot failed: {:?}", e));
+            }
+        }
         end_frame(&mut vulkan_context);
+        // Nothing is presented while minimized, so nothing else limits the loop
+        clock.max_fps = if window.minimized { Some(10.0) } else { None };
+        tick_frame(&mut clock, fence_wait_time(&vulkan_context));
+
+        if let Some(image) = take_capture(&mut vulkan_context) {
+            match image.write_png("screenshot.png") {
+                Ok(_) => utils::trace("Screenshot saved to screenshot.png"),
+                Err(e) => utils::error(format!("Screenshot write failed: {}", e)),
+            }
+        }
     }
 }
@@ -0,0 +1,169 @@
+mod harness;
+
+use harness::*;
+use platform::vulkan::*;
+
+#[test]
+fn triangle() {
+    let mut context = offscreen_context(64, 64);
+    let pipeline_config = PipelineConfig {
+        vertext_shader_source: include_bytes!("../assets/compiled/test.vert.spv"),
+        fragment_shader_source: include_bytes!("../assets/compiled/test.frag.spv"),
+        vertex_layouts: &[],
+        state: PipelineState::default(),
+    };
+    let pipeline = create_pipeline(&mut context, pipeline_config).expect("Pipeline create failed");
+
+    let image = render_frame(&mut context, |context| {
+        draw(context, pipeline, &[], &[], 3, 1).expect("Draw failed");
+    });
+    assert_golden("triangle", &image, Tolerance::default());
+}
+
+#[repr(C)]
+struct MeshVertex {
+    position: [f32; 2],
+}
+platform::impl_vertex!(MeshVertex { position });
+
+#[test]
+fn triangle_from_vertex_buffer() {
+    let mut context = offscreen_context(64, 64);
+    let vertex_layouts = [VertexLayout::of::<MeshVertex>(VertexRate::Vertex)];
+    let pipeline_config = PipelineConfig {
+        vertext_shader_source: include_bytes!("../assets/compiled/mesh.vert.spv"),
+        fragment_shader_source: include_bytes!("../assets/compiled/test.frag.spv"),
+        vertex_layouts: &vertex_layouts,
+        state: PipelineState::default(),
+    };
+    let pipeline = create_pipeline(&mut context, pipeline_config).expect("Pipeline create failed");
+
+    let vertices = [[0.0f32, -0.5], [0.5, 0.5], [-0.5, 0.5]];
+    let bytes: Vec<u8> = vertices.iter().flatten().flat_map(|v| v.to_ne_bytes()).collect();
+    let buffer_config = BufferConfig {
+        size: bytes.len() as u64,
+        usage: BufferUsage { vertex: true, ..Default::default() },
+        host_visible: true,
+    };
+    let buffer = create_buffer(&mut context, buffer_config).expect("Buffer create failed");
+    write_buffer(&mut context, buffer, 0, &bytes).expect("Buffer write failed");
+
+    // Same triangle as the hard-coded positions of test.vert
+    let image = render_frame(&mut context, |context| {
+        bind_vertex_buffers(context, &[buffer]).expect("Bind vertex buffers failed");
+        draw(context, pipeline, &[], &[], 3, 1).expect("Draw failed");
+    });
+    assert_golden("triangle", &image, Tolerance::default());
+}
+
+#[test]
+fn triangle_from_uniform_and_push_constant() {
+    let mut context = offscreen_context(64, 64);
+    let pipeline_config = PipelineConfig {
+        vertext_shader_source: include_bytes!("../assets/compiled/uniform.vert.spv"),
+        fragment_shader_source: include_bytes!("../assets/compiled/tint.frag.spv"),
+        vertex_layouts: &[],
+        state: PipelineState::default(),
+    };
+    let pipeline = create_pipeline(&mut context, pipeline_config).expect("Pipeline create failed");
+
+    // std140 `Transform`: vec2 offset, float scale
+    let transform: Vec<u8> = [0.0f32, 0.0, 0.5, 0.0].iter().flat_map(|v| v.to_ne_bytes()).collect();
+    let buffer_config = BufferConfig {
+        size: transform.len() as u64,
+        usage: BufferUsage { uniform: true, ..Default::default() },
+        host_visible: true,
+    };
+    let uniform = create_buffer(&mut context, buffer_config).expect("Buffer create failed");
+    write_buffer(&mut context, uniform, 0, &transform).expect("Buffer write failed");
+    let tint: Vec<u8> = [1.0f32, 0.0, 0.0, 1.0].iter().flat_map(|v| v.to_ne_bytes()).collect();
+
+    // uniform.vert halves a triangle twice the size of test.vert's, so both values have to
+    // arrive for the output to match
+    let image = render_frame(&mut context, |context| {
+        draw(context, pipeline, &[Binding::Buffer(uniform)], &tint, 3, 1).expect("Draw failed");
+    });
+    assert_golden("triangle", &image, Tolerance::default());
+}
+
+fn storage_buffer(context: &mut VulkanContext, size: u64) -> BufferHandle {
+    let buffer_config = BufferConfig {
+        size,
+        usage: BufferUsage { storage: true, ..Default::default() },
+        host_visible: true,
+    };
+    create_buffer(context, buffer_config).expect("Buffer create failed")
+}
+
+fn read_u32s(context: &mut VulkanContext, buffer: BufferHandle, count: usize) -> Vec<u32> {
+    let mut bytes = vec![0; count * 4];
+    read_buffer(context, buffer, 0, &mut bytes).expect("Buffer read failed");
+    bytes.chunks_exact(4).map(|b| u32::from_ne_bytes(b.try_into().unwrap())).collect()
+}
+
+#[test]
+fn compute_fills_storage_buffers() {
+    const COUNT: usize = 256;
+    let mut context = offscreen_context(64, 64);
+    let config =
+        ComputePipelineConfig { shader_source: include_bytes!("../assets/compiled/fill.comp.spv") };
+    let pipeline = create_compute_pipeline(&mut context, config).expect("Pipeline create failed");
+
+    let direct = storage_buffer(&mut context, COUNT as u64 * 4);
+    let indirect = storage_buffer(&mut context, COUNT as u64 * 4);
+    let arguments_config = BufferConfig {
+        size: 12,
+        usage: BufferUsage { indirect: true, ..Default::default() },
+        host_visible: true,
+    };
+    let arguments = create_buffer(&mut context, arguments_config).expect("Buffer create failed");
+    // VkDispatchIndirectCommand of 4 groups of 64
+    let group_count: Vec<u8> = [4u32, 1, 1].iter().flat_map(|v| v.to_ne_bytes()).collect();
+    write_buffer(&mut context, arguments, 0, &group_count).expect("Buffer write failed");
+
+    compute_frame(&mut context, |context| {
+        let bindings = [Binding::Buffer(direct)];
+        dispatch(context, pipeline, &bindings, &0u32.to_ne_bytes(), [4, 1, 1])
+            .expect("Dispatch failed");
+        let bindings = [Binding::Buffer(indirect)];
+        dispatch_indirect(context, pipeline, &bindings, &1000u32.to_ne_bytes(), arguments, 0)
+            .expect("Indirect dispatch failed");
+        memory_barrier(context, Barrier::ComputeToHost).expect("Barrier failed");
+    });
+
+    let expected: Vec<u32> = (0..COUNT as u32).map(|i| i * 2).collect();
+    assert_eq!(read_u32s(&mut context, direct, COUNT), expected);
+    let expected: Vec<u32> = (0..COUNT as u32).map(|i| 1000 + i * 2).collect();
+    assert_eq!(read_u32s(&mut context, indirect, COUNT), expected);
+}
+
+#[test]
+fn triangle_from_compute() {
+    let mut context = offscreen_context(64, 64);
+    let config = ComputePipelineConfig {
+        shader_source: include_bytes!("../assets/compiled/triangle.comp.spv"),
+    };
+    let compute = create_compute_pipeline(&mut context, config).expect("Pipeline create failed");
+    let pipeline_config = PipelineConfig {
+        vertext_shader_source: include_bytes!("../assets/compiled/storage.vert.spv"),
+        fragment_shader_source: include_bytes!("../assets/compiled/test.frag.spv"),
+        vertex_layouts: &[],
+        state: PipelineState::default(),
+    };
+    let pipeline = create_pipeline(&mut context, pipeline_config).expect("Pipeline create failed");
+    let positions = storage_buffer(&mut context, 3 * 8);
+    let bindings = [Binding::Buffer(positions)];
+
+    // The vertex shader reads the positions written by the dispatch of the same frame
+    let image = render_frame_after_compute(
+        &mut context,
+        |context| {
+            dispatch(context, compute, &bindings, &[], [1, 1, 1]).expect("Dispatch failed");
+            memory_barrier(context, Barrier::ComputeToGraphics).expect("Barrier failed");
+        },
+        |context| {
+            draw(context, pipeline, &bindings, &[], 3, 1).expect("Draw failed");
+        },
+    );
+    assert_golden("triangle", &image, Tolerance::default());
+}
@@ -0,0 +1,127 @@
+//! Golden-image harness: renders through an offscreen `VulkanContext`, reads the frame back
+//! and compares it with a reference PNG from `tests/references`.
+//!
+//! Machines without a GPU can run it on lavapipe by pointing the loader at its ICD, e.g.
+//! `VK_DRIVER_FILES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json cargo test`.
+//! Set `GOLDEN_BLESS=1` to overwrite the references with the current output.
+//! Set `GOLDEN_VALIDATION=1` to run with the validation layer and fail on the first error.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use platform::{utils, vulkan::*};
+
+pub struct Tolerance {
+    /// Largest per-channel difference that still counts as a match
+    pub channel: u8,
+    /// How many pixels may exceed `channel` before the comparison fails
+    pub pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self { channel: 2, pixels: 0 }
+    }
+}
+
+pub fn offscreen_context(width: u32, height: u32) -> VulkanContext {
+    let validation = std::env::var_os("GOLDEN_VALIDATION").is_some();
+    let debug = if validation { DebugConfig::VALIDATION } else { DebugConfig::NONE };
+    let mut context = VulkanContext::new_offscreen(width, height, debug);
+    context.panic_on_validation_error = validation;
+    if let Err(e) = create_offscreen_context(&mut context) {
+        panic!("Offscreen context creation failed: {:?}. Is a Vulkan driver installed?", e);
+    }
+    if validation && !enabled_debug(&context).is_some_and(|debug| debug.validation) {
+        panic!("GOLDEN_VALIDATION is set but the validation layer is not installed");
+    }
+    update_pass(&mut context);
+    context
+}
+
+/// Renders a single frame, `record` is called inside the draw pass
+pub fn render_frame(
+    context: &mut VulkanContext,
+    record: impl FnOnce(&mut VulkanContext),
+) -> CapturedImage {
+    render_frame_after_compute(context, |_| {}, record)
+}
+
+/// Same as `render_frame`, `compute` is called before the draw pass begins
+pub fn render_frame_after_compute(
+    context: &mut VulkanContext,
+    compute: impl FnOnce(&mut VulkanContext),
+    record: impl FnOnce(&mut VulkanContext),
+) -> CapturedImage {
+    begin_frame(context);
+    compute(context);
+    begin_draw_pass(context);
+    record(context);
+    end_draw_pass(context);
+    capture_frame(context).expect("Frame capture failed");
+    end_frame(context);
+    take_capture(context).expect("Captured image is missing")
+}
+
+/// Runs a frame without the draw pass and waits for the GPU to finish it
+pub fn compute_frame(context: &mut VulkanContext, record: impl FnOnce(&mut VulkanContext)) {
+    begin_frame(context);
+    record(context);
+    end_frame(context);
+    wait_idle(context);
+}
+
+pub fn assert_golden(name: &str, image: &CapturedImage, tolerance: Tolerance) {
+    let reference_path = references_dir().join(format!("{name}.png"));
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        std::fs::create_dir_all(references_dir()).expect("Failed to create references dir");
+        image.write_png(&reference_path).expect("Failed to write reference");
+        utils::trace(format!("Reference {} updated", reference_path.display()));
+        return;
+    }
+
+    let reference = std::fs::read(&reference_path).unwrap_or_else(|e| {
+        panic!("Missing reference {}: {e}. Run with GOLDEN_BLESS=1", reference_path.display())
+    });
+    let (width, height, expected) = utils::decode_png(&reference)
+        .unwrap_or_else(|e| panic!("Invalid reference {}: {e}", reference_path.display()));
+    assert_eq!(
+        (width, height),
+        (image.width, image.height),
+        "Size of {name} differs from the reference"
+    );
+
+    let mut mismatched = 0;
+    let mut diff = Vec::with_capacity(expected.len());
+    for (actual, expected) in image.pixels.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        let delta = actual.iter().zip(expected).map(|(a, e)| a.abs_diff(*e)).max().unwrap();
+        if delta > tolerance.channel {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 255, 255]);
+        } else {
+            let luma = (expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 12;
+            diff.extend_from_slice(&[luma as u8, luma as u8, luma as u8, 255]);
+        }
+    }
+
+    if mismatched > tolerance.pixels {
+        let output_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&output_dir).expect("Failed to create output dir");
+        let actual_path = output_dir.join(format!("{name}.actual.png"));
+        let diff_path = output_dir.join(format!("{name}.diff.png"));
+        image.write_png(&actual_path).expect("Failed to write actual image");
+        std::fs::write(&diff_path, utils::encode_png(width, height, &diff))
+            .expect("Failed to write diff image");
+        panic!(
+            "{name}: {mismatched} pixels differ from the reference by more than {}, see {} and {}",
+            tolerance.channel,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn references_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("references")
+}
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames kept for `frame_time_percentile`
+const FRAME_HISTORY: usize = 240;
+/// `std::thread::sleep` can overshoot by about this much, the rest of the wait is spun
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+/// Weight of the newest frame in `FrameClock::fps`
+const FPS_SMOOTHING: f64 = 0.1;
+
+/// Measures frames and optionally caps the frame rate. Call `tick_frame` once per frame
+pub struct FrameClock {
+    /// Frame rate cap, `None` runs as fast as presentation allows
+    pub max_fps: Option<f64>,
+    /// Time between the starts of the last two frames
+    pub delta: Duration,
+    /// Exponentially smoothed frames per second
+    pub fps: f64,
+    /// Number of finished frames
+    pub frame: u64,
+    /// Part of the last frame spent sleeping to respect `max_fps`
+    pub sleep_time: Duration,
+    /// Part of the last frame spent waiting for the GPU, as given to `tick_frame`
+    pub fence_wait: Duration,
+    /// Rest of the last frame
+    pub cpu_time: Duration,
+    frame_start: Instant,
+    frame_times: VecDeque<Duration>,
+}
+
+impl FrameClock {
+    pub fn new(max_fps: Option<f64>) -> Self {
+        Self {
+            max_fps,
+            delta: Duration::ZERO,
+            fps: 0.0,
+            frame: 0,
+            sleep_time: Duration::ZERO,
+            fence_wait: Duration::ZERO,
+            cpu_time: Duration::ZERO,
+            frame_start: Instant::now(),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+// PUBLIC FUNCTIONS =============================================================================
+/// Ends the current frame: sleeps until the frame cap allows the next one and updates the
+/// statistics. `fence_wait` is how long the frame waited for the GPU, see `fence_wait_time`
+pub fn tick_frame(clock: &mut FrameClock, fence_wait: Duration) {
+    clock.sleep_time = match clock.max_fps.filter(|fps| *fps > 0.0) {
+        Some(max_fps) => sleep_until(clock.frame_start + Duration::from_secs_f64(1.0 / max_fps)),
+        None => Duration::ZERO,
+    };
+
+    let now = Instant::now();
+    clock.delta = now - clock.frame_start;
+    clock.frame_start = now;
+    clock.frame += 1;
+    clock.fence_wait = fence_wait;
+    clock.cpu_time = clock.delta.saturating_sub(clock.sleep_time).saturating_sub(fence_wait);
+
+    let fps = 1.0 / clock.delta.as_secs_f64().max(f64::EPSILON);
+    clock.fps = if clock.frame == 1 { fps } else { clock.fps + (fps - clock.fps) * FPS_SMOOTHING };
+
+    if clock.frame_times.len() == FRAME_HISTORY {
+        clock.frame_times.pop_front();
+    }
+    clock.frame_times.push_back(clock.delta);
+}
+
+/// Frame time below which `percentile` (0 to 100) of the recent frames fall, e.g. 99 for
+/// the slowest 1% of frames
+pub fn frame_time_percentile(clock: &FrameClock, percentile: f64) -> Duration {
+    if clock.frame_times.is_empty() {
+        return Duration::ZERO;
+    }
+    let mut frame_times: Vec<_> = clock.frame_times.iter().copied().collect();
+    frame_times.sort_unstable();
+    let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (frame_times.len() - 1) as f64).round();
+    frame_times[rank as usize]
+}
+
+// PRIVATE FUNCTIONS ===========================================================================
+/// Sleeps most of the way and spins the rest, returns the time spent
+fn sleep_until(deadline: Instant) -> Duration {
+    let start = Instant::now();
+    if let Some(remaining) = deadline.checked_duration_since(start) {
+        if remaining > SPIN_THRESHOLD {
+            std::thread::sleep(remaining - SPIN_THRESHOLD);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+    start.elapsed()
+}
@@ -1,3 +1,4 @@
+pub mod clock;
 pub mod utils;
 pub mod vulkan;
 pub mod window;
@@ -1,5 +1,7 @@
 pub use encoding::*;
 pub use log::*;
+pub use png::*;
 
 mod encoding;
 mod log;
+mod png;
@@ -0,0 +1,470 @@
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const MAX_STORED_BLOCK: usize = 0xffff;
+
+/// Encodes tightly packed RGBA8 pixels as a PNG file.
+/// The image data is stored without compression, which keeps the encoder tiny
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), width as usize * height as usize * 4, "Pixel data size mismatch");
+
+    let row_len = width as usize * 4;
+    let mut raw = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in rgba.chunks_exact(row_len.max(1)).take(height as usize) {
+        raw.push(0); // filter type: None
+        raw.extend_from_slice(row);
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]); // 8 bit, RGBA, deflate, adaptive, no interlace
+
+    let mut png = Vec::with_capacity(raw.len() + 128);
+    png.extend_from_slice(&PNG_SIGNATURE);
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Decodes an 8 bit RGB or RGBA, non-interlaced PNG into tightly packed RGBA8 pixels
+pub fn decode_png(png: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    if png.len() < 8 || png[..8] != PNG_SIGNATURE {
+        return Err("Not a PNG file".to_string());
+    }
+
+    let mut header = None;
+    let mut compressed = vec![];
+    let mut offset = 8;
+    while offset + 12 <= png.len() {
+        let len = u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap()) as usize;
+        let kind = &png[offset + 4..offset + 8];
+        let data = png.get(offset + 8..offset + 8 + len).ok_or("Truncated chunk")?;
+        let crc = png.get(offset + 8 + len..offset + 12 + len).ok_or("Truncated chunk")?;
+        if crc32(&png[offset + 4..offset + 8 + len]).to_be_bytes() != crc {
+            return Err(format!("CRC mismatch in {} chunk", String::from_utf8_lossy(kind)));
+        }
+        match kind {
+            b"IHDR" if len == 13 => header = Some(data.to_vec()),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        offset += len + 12;
+    }
+
+    let header = header.ok_or("Missing IHDR chunk")?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let (bit_depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let channels = match color_type {
+        2 => 3,
+        6 => 4,
+        _ => return Err(format!("Unsupported color type {}", color_type)),
+    };
+    if bit_depth != 8 || interlace != 0 {
+        return Err("Only 8 bit non-interlaced images are supported".to_string());
+    }
+
+    let raw = inflate(compressed.get(2..).ok_or("Missing zlib header")?)?;
+    let stride = width as usize * channels;
+    let expected_len = (stride + 1).checked_mul(height as usize).ok_or("Image is too large")?;
+    if raw.len() < expected_len {
+        return Err("Image data is too short".to_string());
+    }
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    let mut previous = vec![0u8; stride];
+    let mut current = vec![0u8; stride];
+    for row in raw.chunks_exact(stride + 1).take(height as usize) {
+        unfilter(row[0], &row[1..], &previous, &mut current, channels)?;
+        for pixel in current.chunks_exact(channels) {
+            pixels.extend_from_slice(&pixel[..3]);
+            pixels.push(if channels == 4 { pixel[3] } else { 255 });
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Ok((width, height, pixels))
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn unfilter(
+    filter: u8,
+    row: &[u8],
+    previous: &[u8],
+    current: &mut [u8],
+    bpp: usize,
+) -> Result<(), String> {
+    for i in 0..row.len() {
+        let left = if i >= bpp { current[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(format!("Unknown filter type {}", filter)),
+        };
+        current[i] = row[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.data.get(self.position / 8).ok_or("Unexpected end of data")?;
+            value |= (((byte >> (self.position % 8)) & 1) as u32) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+}
+
+/// Canonical Huffman code stored as symbol counts per code length
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code".to_string())
+    }
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    const LENGTH_BASE: [u16; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    const LENGTH_EXTRA: [u8; 29] =
+        [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+    const DISTANCE_BASE: [u16; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+        2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+    ];
+    const DISTANCE_EXTRA: [u8; 30] = [
+        0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12,
+        13, 13,
+    ];
+    const CODE_LENGTH_ORDER: [usize; 19] =
+        [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+    let mut reader = BitReader { data, position: 0 };
+    let mut out = vec![];
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let start = reader.position / 8;
+                let header = data.get(start..start + 4).ok_or("Truncated stored block")?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("Stored block length check failed".to_string());
+                }
+                let len = len as usize;
+                let block = data.get(start + 4..start + 4 + len).ok_or("Truncated stored block")?;
+                out.extend_from_slice(block);
+                reader.position = (start + 4 + len) * 8;
+            }
+            kind @ (1 | 2) => {
+                let (literals, distances) = if kind == 1 {
+                    let mut lengths = [8u8; 288];
+                    lengths[144..256].fill(9);
+                    lengths[256..280].fill(7);
+                    (Huffman::new(&lengths), Huffman::new(&[5u8; 30]))
+                } else {
+                    let literal_count = reader.bits(5)? as usize + 257;
+                    let distance_count = reader.bits(5)? as usize + 1;
+                    let code_count = reader.bits(4)? as usize + 4;
+                    let mut code_lengths = [0u8; 19];
+                    for &index in CODE_LENGTH_ORDER.iter().take(code_count) {
+                        code_lengths[index] = reader.bits(3)? as u8;
+                    }
+                    let code = Huffman::new(&code_lengths);
+                    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                    while lengths.len() < literal_count + distance_count {
+                        let (value, repeat) = match code.decode(&mut reader)? {
+                            symbol @ 0..=15 => (symbol as u8, 1),
+                            16 => (
+                                *lengths.last().ok_or("Repeat without length")?,
+                                3 + reader.bits(2)?,
+                            ),
+                            17 => (0, 3 + reader.bits(3)?),
+                            _ => (0, 11 + reader.bits(7)?),
+                        };
+                        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                    }
+                    (
+                        Huffman::new(&lengths[..literal_count]),
+                        Huffman::new(&lengths[literal_count..literal_count + distance_count]),
+                    )
+                };
+
+                loop {
+                    let symbol = literals.decode(&mut reader)? as usize;
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let index = symbol - 257;
+                    if index >= LENGTH_BASE.len() {
+                        return Err("Invalid length symbol".to_string());
+                    }
+                    let length = LENGTH_BASE[index] as usize
+                        + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                    let index = distances.decode(&mut reader)? as usize;
+                    if index >= DISTANCE_BASE.len() {
+                        return Err("Invalid distance symbol".to_string());
+                    }
+                    let distance = DISTANCE_BASE[index] as usize
+                        + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err("Distance is too far back".to_string());
+                    }
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+            _ => return Err("Invalid block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut n = 0;
+        while n < 256 {
+            let mut c = n as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+                k += 1;
+            }
+            table[n] = c;
+            n += 1;
+        }
+        table
+    };
+
+    let mut crc = 0xffffffffu32;
+    for &byte in data {
+        crc = TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xffffffff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Zlib stream of a 4x3 RGB image with Sub, Up and Paeth filtered rows,
+    /// compressed with fixed Huffman codes
+    const FIXED_HUFFMAN_RGB: [u8; 26] = [
+        0x78, 0x01, 0x63, 0x64, 0x60, 0x68, 0xe0, 0x60, 0x60, 0x80, 0x20, 0x26, 0x06, 0x38, 0x93,
+        0x83, 0x81, 0x05, 0x44, 0xc1, 0x00, 0x00, 0x18, 0x7e, 0x00, 0xc8,
+    ];
+    /// Zlib stream of an 8x8 RGBA image using every filter type, compressed with dynamic
+    /// Huffman codes
+    const DYNAMIC_HUFFMAN_RGBA: [u8; 75] = [
+        0x78, 0xda, 0xa5, 0xce, 0xa1, 0x12, 0xc0, 0x20, 0x0c, 0x04, 0xd1, 0xa3, 0x45, 0x20, 0x23,
+        0x91, 0xc8, 0xc8, 0x48, 0x24, 0x9f, 0xc6, 0x9f, 0xb7, 0x4b, 0x3d, 0x98, 0x66, 0xe6, 0xa9,
+        0x9c, 0x58, 0x49, 0xf3, 0x29, 0x30, 0x54, 0x34, 0x38, 0x02, 0x1d, 0x49, 0x65, 0x0d, 0xa4,
+        0x9d, 0x4b, 0xa7, 0x2f, 0x6e, 0xd9, 0x98, 0x39, 0x4b, 0x5b, 0xdf, 0xf2, 0x78, 0x4e, 0x03,
+        0x0c, 0x15, 0x0d, 0x8e, 0x40, 0xf7, 0x15, 0x19, 0x3f, 0x23, 0x5f, 0xb2, 0xd6, 0x20, 0x34,
+    ];
+
+    /// Pixels both compressed images were made from
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| [x as u8 * 8, y as u8 * 8, 128, 255]))
+            .collect()
+    }
+
+    fn png_with_idat(width: u32, height: u32, color_type: u8, idat: &[u8]) -> Vec<u8> {
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[8, color_type, 0, 0, 0]);
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &ihdr);
+        write_chunk(&mut png, b"IDAT", idat);
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn round_trip() {
+        let pixels: Vec<u8> = (0..5 * 7 * 4).map(|i| (i * 13) as u8).collect();
+        let decoded = decode_png(&encode_png(5, 7, &pixels)).unwrap();
+        assert_eq!(decoded, (5, 7, pixels));
+    }
+
+    #[test]
+    fn round_trip_multiple_stored_blocks() {
+        let pixels: Vec<u8> = (0..200 * 100 * 4).map(|i| (i % 251) as u8).collect();
+        let decoded = decode_png(&encode_png(200, 100, &pixels)).unwrap();
+        assert_eq!(decoded, (200, 100, pixels));
+    }
+
+    #[test]
+    fn decodes_fixed_huffman() {
+        let png = png_with_idat(4, 3, 2, &FIXED_HUFFMAN_RGB);
+        assert_eq!(decode_png(&png).unwrap(), (4, 3, gradient(4, 3)));
+    }
+
+    #[test]
+    fn decodes_dynamic_huffman() {
+        let png = png_with_idat(8, 8, 6, &DYNAMIC_HUFFMAN_RGBA);
+        assert_eq!(decode_png(&png).unwrap(), (8, 8, gradient(8, 8)));
+    }
+
+    #[test]
+    fn rejects_bad_crc() {
+        let mut png = encode_png(2, 2, &[7; 16]);
+        let last = png.len() - 1;
+        png[last] ^= 0xff;
+        assert_eq!(decode_png(&png).unwrap_err(), "CRC mismatch in IEND chunk");
+    }
+
+    #[test]
+    fn rejects_corrupt_data() {
+        let mut png = encode_png(2, 2, &[7; 16]);
+        // First pixel byte inside IDAT, the chunk CRC catches it
+        png[8 + 25 + 8 + 8] ^= 1;
+        assert_eq!(decode_png(&png).unwrap_err(), "CRC mismatch in IDAT chunk");
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        let png = encode_png(2, 2, &[7; 16]);
+        assert_eq!(decode_png(&png[..png.len() - 20]).unwrap_err(), "Truncated chunk");
+
+        let mut long_chunk = png.clone();
+        long_chunk[8..12].copy_from_slice(&1000u32.to_be_bytes());
+        assert_eq!(decode_png(&long_chunk).unwrap_err(), "Truncated chunk");
+
+        let mut stored = zlib_stored(&[0; 18]);
+        stored[5] ^= 1; // NLEN no longer complements LEN
+        let png = png_with_idat(2, 2, 6, &stored);
+        assert_eq!(decode_png(&png).unwrap_err(), "Stored block length check failed");
+
+        let short = png_with_idat(4, 4, 6, &zlib_stored(&[0; 18]));
+        assert_eq!(decode_png(&short).unwrap_err(), "Image data is too short");
+    }
+
+    #[test]
+    fn rejects_truncated_deflate() {
+        let png = png_with_idat(8, 8, 6, &DYNAMIC_HUFFMAN_RGBA[..40]);
+        assert!(decode_png(&png).is_err());
+    }
+}
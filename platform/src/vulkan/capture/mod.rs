@@ -0,0 +1,213 @@
+use std::path::Path;
+
+use ash::vk;
+
+use super::{allocate_memory, set_object_name, InternalContext, VulkanError};
+use crate::utils;
+
+/// Frame contents read back from the GPU, always tightly packed RGBA8
+#[derive(Debug, Clone)]
+pub struct CapturedImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl CapturedImage {
+    pub fn write_png(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, utils::encode_png(self.width, self.height, &self.pixels))
+    }
+}
+
+pub struct Readback {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub size: vk::DeviceSize,
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
+    pub pending: bool,
+}
+
+/// Records a copy of `image` into the readback buffer of the context.
+/// The image is expected in `layout` and is returned to it after the copy
+pub fn record_readback(
+    context: &mut InternalContext,
+    image: vk::Image,
+    layout: vk::ImageLayout,
+    extent: vk::Extent2D,
+    format: vk::Format,
+) -> Result<(), VulkanError> {
+    if !supports_readback(format) {
+        return Err(VulkanError::CaptureNotSupported);
+    }
+    let size = extent.width as vk::DeviceSize * extent.height as vk::DeviceSize * 4;
+    if context.readback.as_ref().is_some_and(|r| r.size != size) {
+        if let Some(readback) = context.readback.take() {
+            destroy_readback(&readback, &context.device);
+        }
+    }
+    if context.readback.is_none() {
+        context.readback = Some(create_readback(context, size)?);
+    }
+
+    let command_buffer = context.frames[context.current_frame].command_buffer;
+    let readback = context.readback.as_mut().unwrap();
+    readback.extent = extent;
+    readback.format = format;
+    readback.pending = true;
+
+    let subresource_range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+    let to_transfer = vk::ImageMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .old_layout(layout)
+        .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range);
+    let from_transfer = vk::ImageMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .dst_access_mask(vk::AccessFlags::empty())
+        .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .new_layout(layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range);
+    let host_read = vk::BufferMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(readback.buffer)
+        .size(vk::WHOLE_SIZE);
+    let region = vk::BufferImageCopy::default()
+        .image_subresource(vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .image_extent(vk::Extent3D { width: extent.width, height: extent.height, depth: 1 });
+
+    unsafe {
+        context.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_transfer],
+        );
+        context.device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            readback.buffer,
+            &[region],
+        );
+        context.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE | vk::PipelineStageFlags::HOST,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[host_read],
+            &[from_transfer],
+        );
+    }
+    Ok(())
+}
+
+/// Reads the finished copy back into CPU memory.
+/// Must only be called after the command buffer with the copy has completed
+pub fn resolve_readback(context: &mut InternalContext) -> Option<CapturedImage> {
+    let readback = context.readback.as_mut()?;
+    if !readback.pending {
+        return None;
+    }
+    readback.pending = false;
+
+    let mut pixels = vec![0u8; readback.size as usize];
+    unsafe {
+        let mapped = context
+            .device
+            .map_memory(readback.memory, 0, readback.size, vk::MemoryMapFlags::empty())
+            .expect("Map readback memory failed");
+        std::ptr::copy_nonoverlapping(mapped as *const u8, pixels.as_mut_ptr(), pixels.len());
+        context.device.unmap_memory(readback.memory);
+    }
+    if is_bgra(readback.format) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Some(CapturedImage { width: readback.extent.width, height: readback.extent.height, pixels })
+}
+
+pub fn destroy_readback(readback: &Readback, device: &ash::Device) {
+    unsafe {
+        device.destroy_buffer(readback.buffer, None);
+        device.free_memory(readback.memory, None);
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn create_readback(
+    context: &InternalContext,
+    size: vk::DeviceSize,
+) -> Result<Readback, VulkanError> {
+    let create_info = vk::BufferCreateInfo::default()
+        .size(size)
+        .usage(vk::BufferUsageFlags::TRANSFER_DST)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    unsafe {
+        let buffer = context
+            .device
+            .create_buffer(&create_info, None)
+            .map_err(|_| VulkanError::ResourceCreationFailed)?;
+        let requirements = context.device.get_buffer_memory_requirements(buffer);
+        let memory = allocate_memory(
+            &context.device,
+            &context.memory_properties,
+            requirements,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
+        .inspect_err(|_| context.device.destroy_buffer(buffer, None))?;
+        context.device.bind_buffer_memory(buffer, memory, 0).expect("Bind readback memory failed");
+        set_object_name(context, buffer, "Readback buffer");
+        set_object_name(context, memory, "Readback memory");
+
+        Ok(Readback {
+            buffer,
+            memory,
+            size,
+            extent: vk::Extent2D::default(),
+            format: vk::Format::UNDEFINED,
+            pending: false,
+        })
+    }
+}
+
+fn supports_readback(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8G8B8A8_UNORM
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::A8B8G8R8_UNORM_PACK32
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+    ) || is_bgra(format)
+}
+
+fn is_bgra(format: vk::Format) -> bool {
+    matches!(format, vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB)
+}
@@ -3,3 +3,10 @@ use std::ffi::CStr;
 pub const FRAMES_IN_FLIGHT: usize = 2;
 pub const VALIDATION_NAME: &CStr =
     unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") };
+pub const PIPELINE_CACHE_FILE: &str = "pipeline_cache.bin";
+/// Validation messages kept by the context, older ones are dropped
+pub const MAX_VALIDATION_MESSAGES: usize = 1024;
+/// Timestamp queries per frame, two per GPU scope
+pub const MAX_TIMESTAMP_QUERIES: u32 = 128;
+/// Resolved frames kept per GPU scope
+pub const GPU_HISTORY_LENGTH: usize = 240;
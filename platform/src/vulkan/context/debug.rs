@@ -0,0 +1,104 @@
+use std::ffi::CString;
+
+use ash::vk;
+
+use super::InternalContext;
+use crate::utils;
+
+/// Names `object` for validation messages and capture tools.
+/// Does nothing unless `VK_EXT_debug_utils` is enabled with `DebugConfig::debug_utils`
+pub fn set_object_name<T: vk::Handle>(context: &InternalContext, object: T, name: &str) {
+    let Some(debug_utils) = context.debug_utils_device.as_ref() else {
+        return;
+    };
+    let Ok(name) = CString::new(name) else {
+        return;
+    };
+    let name_info =
+        vk::DebugUtilsObjectNameInfoEXT::default().object_handle(object).object_name(&name);
+    if let Err(e) = unsafe { debug_utils.set_debug_utils_object_name(&name_info) } {
+        utils::error(format!("Cant name {:?}: {}", name, e));
+    }
+}
+
+/// Opens a label region in `command_buffer`, closed by `end_label`. Regions can be nested
+pub fn begin_label(
+    context: &InternalContext,
+    command_buffer: vk::CommandBuffer,
+    name: &str,
+    color: [f32; 4],
+) {
+    let Some(debug_utils) = context.debug_utils_device.as_ref() else {
+        return;
+    };
+    let Ok(name) = CString::new(name) else {
+        return;
+    };
+    let label = vk::DebugUtilsLabelEXT::default().label_name(&name).color(color);
+    unsafe { debug_utils.cmd_begin_debug_utils_label(command_buffer, &label) };
+}
+
+pub fn end_label(context: &InternalContext, command_buffer: vk::CommandBuffer) {
+    if let Some(debug_utils) = context.debug_utils_device.as_ref() {
+        unsafe { debug_utils.cmd_end_debug_utils_label(command_buffer) };
+    }
+}
+
+/// Single label marking a point in `command_buffer`
+pub fn insert_label(
+    context: &InternalContext,
+    command_buffer: vk::CommandBuffer,
+    name: &str,
+    color: [f32; 4],
+) {
+    let Some(debug_utils) = context.debug_utils_device.as_ref() else {
+        return;
+    };
+    let Ok(name) = CString::new(name) else {
+        return;
+    };
+    let label = vk::DebugUtilsLabelEXT::default().label_name(&name).color(color);
+    unsafe { debug_utils.cmd_insert_debug_utils_label(command_buffer, &label) };
+}
+
+/// Names the per-frame objects and the swapchain images of a freshly created context
+pub fn name_context_objects(context: &InternalContext) {
+    set_object_name(context, context.command_pool, "Command pool");
+    for (index, frame) in context.frames.iter().enumerate() {
+        set_object_name(context, frame.command_buffer, &format!("Frame {} commands", index));
+        set_object_name(context, frame.reuse_fence, &format!("Frame {} reuse fence", index));
+        set_object_name(
+            context,
+            frame.rendering_complete_semaphore,
+            &format!("Frame {} rendering complete", index),
+        );
+        set_object_name(
+            context,
+            frame.presentation_complete_semaphore,
+            &format!("Frame {} presentation complete", index),
+        );
+        for (pool_index, pool) in frame.descriptor_pools.iter().enumerate() {
+            set_object_name(
+                context,
+                *pool,
+                &format!("Frame {} descriptor pool {}", index, pool_index),
+            );
+        }
+        if let Some(profiler) = context.profiler.as_ref() {
+            let query_pool = profiler.frame(index).query_pool;
+            set_object_name(context, query_pool, &format!("Frame {} timestamps", index));
+        }
+    }
+    name_swapchain_objects(context);
+}
+
+/// Names the swapchain images and their views, call again after the swapchain is recreated
+pub fn name_swapchain_objects(context: &InternalContext) {
+    let target = if context.offscreen.is_some() { "Offscreen target" } else { "Swapchain image" };
+    for (index, image) in context.swapchain_images.iter().enumerate() {
+        set_object_name(context, *image, &format!("{} {}", target, index));
+    }
+    for (index, view) in context.swapchain_image_views.iter().enumerate() {
+        set_object_name(context, *view, &format!("{} {} view", target, index));
+    }
+}
@@ -1,19 +1,18 @@
-use std::ffi::{c_char, CString};
+use std::ffi::{c_char, CStr, CString};
 
 use ash::{ext::debug_utils, khr::*, vk};
 
+use super::DebugConfig;
 use crate::{utils, vulkan::constants::VALIDATION_NAME, vulkan::VulkanError};
 
+/// Creates the instance with the parts of `debug` the driver supports.
+/// Returns which of them were actually enabled
 pub unsafe fn create_instance(
-    validation: bool,
-) -> Result<(ash::Entry, ash::Instance), VulkanError> {
-    let entry = unsafe { ash::Entry::load().expect("Vulkan not supported") };
-
-    let validation_support = get_validation_support(&entry);
-    if !validation_support && validation {
-        utils::error("Validation requested but not present");
-        return Err(VulkanError::ValidationNotPresent);
-    }
+    debug: DebugConfig,
+    surface: bool,
+) -> Result<(ash::Entry, ash::Instance, DebugConfig), VulkanError> {
+    let entry = unsafe { ash::Entry::load().map_err(|_| VulkanError::VulkanNotSupported)? };
+    let debug = supported_debug_config(&entry, debug);
 
     let instance = unsafe {
         let engine_name = CString::new("Best Engine").unwrap();
@@ -22,8 +21,8 @@ pub unsafe fn create_instance(
             .engine_name(&engine_name)
             .engine_version(1)
             .application_version(1);
-        let layers = get_layers(validation);
-        let extensions = get_required_extensions(validation);
+        let layers = get_layers(debug.validation);
+        let extensions = get_required_extensions(debug.debug_utils, surface);
 
         let create_info = vk::InstanceCreateInfo::default()
             .application_info(&app_info)
@@ -34,7 +33,27 @@ pub unsafe fn create_instance(
         entry.create_instance(&create_info, None).expect("Instance create error")
     };
 
-    Ok((entry, instance))
+    Ok((entry, instance, debug))
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn supported_debug_config(entry: &ash::Entry, requested: DebugConfig) -> DebugConfig {
+    let validation = requested.validation && get_validation_support(entry);
+    if requested.validation && !validation {
+        utils::error("Validation requested but not present, continuing without it");
+    }
+    // The validation layer provides debug utils as well, even when the driver doesn't
+    let debug_utils = requested.debug_utils
+        && (has_extension(entry, None, debug_utils::NAME)
+            || (validation && has_extension(entry, Some(VALIDATION_NAME), debug_utils::NAME)));
+    if requested.debug_utils && !debug_utils {
+        utils::error("VK_EXT_debug_utils requested but not present, continuing without it");
+    }
+    DebugConfig {
+        validation,
+        debug_utils,
+        min_severity: requested.min_severity.filter(|_| debug_utils),
+    }
 }
 
 fn get_layers(validation: bool) -> Vec<*const c_char> {
@@ -45,9 +64,13 @@ fn get_layers(validation: bool) -> Vec<*const c_char> {
     }
 }
 
-fn get_required_extensions(validation: bool) -> Vec<*const c_char> {
-    let mut extensions = vec![surface::NAME.as_ptr(), win32_surface::NAME.as_ptr()];
-    if validation {
+fn get_required_extensions(debug_utils: bool, surface: bool) -> Vec<*const c_char> {
+    let mut extensions = vec![];
+    if surface {
+        extensions.push(surface::NAME.as_ptr());
+        extensions.push(win32_surface::NAME.as_ptr());
+    }
+    if debug_utils {
         extensions.push(debug_utils::NAME.as_ptr())
     }
 
@@ -66,3 +89,9 @@ fn get_validation_support(entry: &ash::Entry) -> bool {
         }
     })
 }
+
+fn has_extension(entry: &ash::Entry, layer: Option<&CStr>, extension: &CStr) -> bool {
+    let extension_properties =
+        unsafe { entry.enumerate_instance_extension_properties(layer).unwrap_or_default() };
+    extension_properties.iter().any(|e| e.extension_name_as_c_str() == Ok(extension))
+}
@@ -1,14 +1,28 @@
 use ash::{ext::debug_utils, khr::*, vk};
-use std::{borrow::Cow, ffi::CStr};
-
-use super::{constants::FRAMES_IN_FLIGHT, create_framebuffers, Pass, VulkanError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{
+    constants::FRAMES_IN_FLIGHT, create_depth_target, create_framebuffers, create_gpu_profiler,
+    destroy_depth_target, destroy_gpu_profiler, destroy_readback, CapturedImage, GpuProfiler, Pass,
+    Readback, VulkanError,
+};
 use crate::{utils, window::Window};
 
+use debug::*;
 use instance::*;
+use offscreen::*;
+use validation::*;
+pub mod debug;
 pub mod instance;
+pub mod offscreen;
+pub mod pipeline_cache;
+pub mod validation;
 
 pub struct InternalContext {
     pub out_of_date: bool,
+    /// The window is minimized, so its surface has no size and frames are skipped
+    pub minimized: bool,
 
     _entry: ash::Entry,
     pub instance: ash::Instance,
@@ -21,19 +35,44 @@ pub struct InternalContext {
 
     pub device: ash::Device,
     pub physical_device: vk::PhysicalDevice,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    /// Optional features that were available and enabled on the device
+    pub features: vk::PhysicalDeviceFeatures,
     pub present_queue: vk::Queue,
     pub command_pool: vk::CommandPool,
+    /// Null until `load_pipeline_cache` is called
+    pub pipeline_cache: vk::PipelineCache,
 
     pub swapchain_loader: swapchain::Device,
     pub swapchain: vk::SwapchainKHR,
+    pub swapchain_usage: vk::ImageUsageFlags,
+    pub swapchain_images: Vec<vk::Image>,
     pub swapchain_image_views: Vec<vk::ImageView>,
+    /// Layout the render target is left in at the end of a frame
+    pub target_layout: vk::ImageLayout,
+    pub offscreen: Option<OffscreenTarget>,
 
     pub current_frame: usize,
     pub frames: [Frame; FRAMES_IN_FLIGHT],
     pub present_index: Option<u32>,
-
+    /// A pass rendered into the image of the current frame, so it is in `target_layout`
+    pub target_drawn: bool,
+    /// Time the last `begin_frame` spent waiting on `reuse_fence`
+    pub fence_wait: Duration,
+
+    pub readback: Option<Readback>,
+    pub captured: Option<CapturedImage>,
+    /// `None` if the queue has no timestamp support
+    pub profiler: Option<GpuProfiler>,
+
+    /// Debug facilities that were requested and are supported
+    pub debug: DebugConfig,
     pub debug_utils_loader: debug_utils::Instance,
     pub debug_messenger: vk::DebugUtilsMessengerEXT,
+    /// Filled by the debug messenger, kept here so it outlives the messenger
+    _validation_log: Arc<ValidationLog>,
+    /// Object names and command buffer labels, `None` without `DebugConfig::debug_utils`
+    pub debug_utils_device: Option<debug_utils::Device>,
 }
 
 pub struct Frame {
@@ -41,19 +80,30 @@ pub struct Frame {
     pub reuse_fence: vk::Fence,
     pub rendering_complete_semaphore: vk::Semaphore,
     pub presentation_complete_semaphore: vk::Semaphore,
+    /// Transient descriptor sets of the frame, reset once `reuse_fence` signals.
+    /// Another pool is chained when the ones before run out
+    pub descriptor_pools: Vec<vk::DescriptorPool>,
+    /// Pool the next sets are allocated from, the ones before it are full
+    pub descriptor_pool_index: usize,
 }
 
-pub fn create_context(window: &Window, validation: bool) -> Result<InternalContext, VulkanError> {
+pub fn create_context(
+    window: &Window,
+    debug: DebugConfig,
+    validation_log: Arc<ValidationLog>,
+) -> Result<InternalContext, VulkanError> {
     if window.hwnd() == 0 || window.hinstance() == 0 {
         return Err(VulkanError::WindowNotInitialized);
     }
-    let (entry, instance) = unsafe { create_instance(validation)? };
+    let (entry, instance, debug) = unsafe { create_instance(debug, true)? };
 
     let surface = create_surface(&entry, &instance, window);
     let surface_loader = surface::Instance::new(&entry, &instance);
     let (physical_device, queue_family_index) =
         unsafe { pick_physical_device(&instance, &surface_loader, surface) };
     let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
 
     utils::trace(format!(
         "Picked device: {:?}",
@@ -66,7 +116,7 @@ pub fn create_context(window: &Window, validation: bool) -> Result<InternalConte
         .queue_priorities(&priorities)];
 
     let device_extensions = [swapchain::NAME.as_ptr()];
-    let device_features = vk::PhysicalDeviceFeatures::default();
+    let device_features = unsafe { pick_features(&instance, physical_device) };
     let create_info = vk::DeviceCreateInfo::default()
         .enabled_extension_names(&device_extensions)
         .enabled_features(&device_features)
@@ -106,6 +156,9 @@ pub fn create_context(window: &Window, validation: bool) -> Result<InternalConte
             .get_physical_device_surface_present_modes(physical_device, surface)
             .expect("No present modes")
     };
+    // TRANSFER_SRC is needed to read frames back for screenshots
+    let swapchain_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+        | (surface_capabilities.supported_usage_flags & vk::ImageUsageFlags::TRANSFER_SRC);
     let present_mode = if supported_present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
         vk::PresentModeKHR::MAILBOX
     } else {
@@ -116,7 +169,7 @@ pub fn create_context(window: &Window, validation: bool) -> Result<InternalConte
         .surface(surface)
         .min_image_count(desired_image_count)
         .present_mode(present_mode)
-        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+        .image_usage(swapchain_usage)
         .image_extent(image_extent)
         .image_color_space(surface_format.color_space)
         .image_format(surface_format.format)
@@ -144,75 +197,53 @@ pub fn create_context(window: &Window, validation: bool) -> Result<InternalConte
     let swapchain_image_views =
         create_swapchain_image_views(&device, &present_images, surface_format.format);
 
-    let command_buffer_allocate_info =
-        vk::CommandBufferAllocateInfo::default().command_pool(command_pool).command_buffer_count(1);
-    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
-    let semaphore_create_info = vk::SemaphoreCreateInfo::default();
-
-    let frames = std::array::from_fn(|_| {
-        let command_buffer = unsafe {
-            device
-                .allocate_command_buffers(&command_buffer_allocate_info)
-                .expect("Cant allocate command buffer")[0]
-        };
-        let reuse_fence = unsafe {
-            device.create_fence(&fence_create_info, None).expect("Failed to create fence")
-        };
-        let rendering_complete_semaphore = unsafe {
-            device.create_semaphore(&semaphore_create_info, None).expect("Cant create semaphore")
-        };
-        let presentation_complete_semaphore = unsafe {
-            device.create_semaphore(&semaphore_create_info, None).expect("Cant create semaphore")
-        };
-        Frame {
-            command_buffer,
-            reuse_fence,
-            rendering_complete_semaphore,
-            presentation_complete_semaphore,
-        }
-    });
-
-    let create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-        .message_severity(
-            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
-        )
-        .message_type(
-            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-        )
-        .pfn_user_callback(Some(vulkan_debug_callback));
+    let frames = create_frames(&device, command_pool);
+    let profiler = create_gpu_profiler(&instance, physical_device, queue_family_index, &device);
     let debug_utils_loader = debug_utils::Instance::new(&entry, &instance);
-    let debug_messenger = unsafe {
-        debug_utils_loader
-            .create_debug_utils_messenger(&create_info, None)
-            .expect("Debug callback error")
-    };
+    let debug_messenger = create_debug_messenger(&debug_utils_loader, debug, &validation_log);
+    let debug_utils_device =
+        debug.debug_utils.then(|| debug_utils::Device::new(&instance, &device));
 
-    Ok(InternalContext {
+    let context = InternalContext {
         out_of_date: false,
+        minimized: false,
         _entry: entry,
         instance,
         surface,
         surface_loader,
         device,
         physical_device,
+        memory_properties,
+        features: device_features,
         present_queue,
+        pipeline_cache: vk::PipelineCache::null(),
         swapchain_loader,
         swapchain,
+        swapchain_usage,
+        swapchain_images: present_images,
         command_pool,
         swapchain_image_views,
+        target_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        offscreen: None,
         surface_format,
         surface_resolution: image_extent,
         present_mode,
+        debug,
         debug_utils_loader,
         debug_messenger,
+        _validation_log: validation_log,
+        debug_utils_device,
         current_frame: 0,
         present_index: None,
+        target_drawn: false,
+        fence_wait: Duration::ZERO,
         frames,
-    })
+        readback: None,
+        captured: None,
+        profiler,
+    };
+    name_context_objects(&context);
+    Ok(context)
 }
 
 pub unsafe fn destroy_context(context: &mut InternalContext) {
@@ -221,20 +252,161 @@ pub unsafe fn destroy_context(context: &mut InternalContext) {
         context.device.destroy_semaphore(f.rendering_complete_semaphore, None);
         context.device.destroy_semaphore(f.presentation_complete_semaphore, None);
         context.device.destroy_fence(f.reuse_fence, None);
+        for pool in f.descriptor_pools.iter() {
+            context.device.destroy_descriptor_pool(*pool, None);
+        }
     }
     for image_view in context.swapchain_image_views.iter() {
         context.device.destroy_image_view(*image_view, None);
     }
     context.device.destroy_command_pool(context.command_pool, None);
+    if context.pipeline_cache != vk::PipelineCache::null() {
+        context.device.destroy_pipeline_cache(context.pipeline_cache, None);
+    }
+    if let Some(readback) = context.readback.take() {
+        destroy_readback(&readback, &context.device);
+    }
+    if let Some(profiler) = context.profiler.take() {
+        destroy_gpu_profiler(&profiler, &context.device);
+    }
 
-    context.debug_utils_loader.destroy_debug_utils_messenger(context.debug_messenger, None);
+    if context.debug_messenger != vk::DebugUtilsMessengerEXT::null() {
+        context.debug_utils_loader.destroy_debug_utils_messenger(context.debug_messenger, None);
+    }
 
-    context.swapchain_loader.destroy_swapchain(context.swapchain, None);
-    context.device.destroy_device(None);
-    context.surface_loader.destroy_surface(context.surface, None);
+    if let Some(offscreen) = context.offscreen.take() {
+        destroy_offscreen_target(&offscreen, &context.device);
+        context.device.destroy_device(None);
+    } else {
+        context.swapchain_loader.destroy_swapchain(context.swapchain, None);
+        context.device.destroy_device(None);
+        context.surface_loader.destroy_surface(context.surface, None);
+    }
     context.instance.destroy_instance(None);
 }
 
+pub fn create_frames(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+) -> [Frame; FRAMES_IN_FLIGHT] {
+    let command_buffer_allocate_info =
+        vk::CommandBufferAllocateInfo::default().command_pool(command_pool).command_buffer_count(1);
+    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+    let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+
+    std::array::from_fn(|_| {
+        let command_buffer = unsafe {
+            device
+                .allocate_command_buffers(&command_buffer_allocate_info)
+                .expect("Cant allocate command buffer")[0]
+        };
+        let reuse_fence = unsafe {
+            device.create_fence(&fence_create_info, None).expect("Failed to create fence")
+        };
+        let rendering_complete_semaphore = unsafe {
+            device.create_semaphore(&semaphore_create_info, None).expect("Cant create semaphore")
+        };
+        let presentation_complete_semaphore = unsafe {
+            device.create_semaphore(&semaphore_create_info, None).expect("Cant create semaphore")
+        };
+        let descriptor_pool = create_descriptor_pool(device).expect("Cant create descriptor pool");
+        Frame {
+            command_buffer,
+            reuse_fence,
+            rendering_complete_semaphore,
+            presentation_complete_semaphore,
+            descriptor_pools: vec![descriptor_pool],
+            descriptor_pool_index: 0,
+        }
+    })
+}
+
+/// Allocates sets for the current frame, chaining a new pool when the current one is full
+pub fn allocate_descriptor_sets(
+    context: &mut InternalContext,
+    set_layouts: &[vk::DescriptorSetLayout],
+) -> Result<Vec<vk::DescriptorSet>, VulkanError> {
+    loop {
+        let frame = &mut context.frames[context.current_frame];
+        let fresh_pool = frame.descriptor_pool_index == frame.descriptor_pools.len();
+        if fresh_pool {
+            let pool = create_descriptor_pool(&context.device)?;
+            frame.descriptor_pools.push(pool);
+            let name = format!(
+                "Frame {} descriptor pool {}",
+                context.current_frame, frame.descriptor_pool_index
+            );
+            set_object_name(context, pool, &name);
+        }
+
+        let frame = &mut context.frames[context.current_frame];
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(frame.descriptor_pools[frame.descriptor_pool_index])
+            .set_layouts(set_layouts);
+        match unsafe { context.device.allocate_descriptor_sets(&allocate_info) } {
+            Ok(sets) => return Ok(sets),
+            // Sets that don't fit in an empty pool never will
+            Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL)
+                if !fresh_pool =>
+            {
+                frame.descriptor_pool_index += 1;
+            }
+            Err(_) => return Err(VulkanError::ResourceCreationFailed),
+        }
+    }
+}
+
+/// Frees the sets of `frame`, its fence must have signaled
+pub fn reset_descriptor_pools(context: &mut InternalContext, frame: usize) {
+    let frame = &mut context.frames[frame];
+    for pool in frame.descriptor_pools.iter() {
+        unsafe {
+            context
+                .device
+                .reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())
+                .expect("Descriptor pool reset failed");
+        }
+    }
+    frame.descriptor_pool_index = 0;
+}
+
+/// The messenger relies on `VK_EXT_debug_utils`, so `debug` must only contain what
+/// `create_instance` enabled. Messages are collected into `validation_log`
+pub fn create_debug_messenger(
+    debug_utils_loader: &debug_utils::Instance,
+    debug: DebugConfig,
+    validation_log: &Arc<ValidationLog>,
+) -> vk::DebugUtilsMessengerEXT {
+    let Some(min_severity) = debug.min_severity else {
+        return vk::DebugUtilsMessengerEXT::null();
+    };
+    let create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
+        .message_severity(min_severity.and_above())
+        .message_type(
+            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        )
+        .pfn_user_callback(Some(vulkan_debug_callback))
+        .user_data(Arc::as_ptr(validation_log) as *mut _);
+    unsafe { debug_utils_loader.create_debug_utils_messenger(&create_info, None) }.unwrap_or_else(
+        |e| {
+            utils::error(format!("Cant create debug messenger: {}", e));
+            vk::DebugUtilsMessengerEXT::null()
+        },
+    )
+}
+
+/// Enables the optional features used by pipeline states if the device has them
+pub unsafe fn pick_features(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> vk::PhysicalDeviceFeatures {
+    let supported = instance.get_physical_device_features(physical_device);
+    vk::PhysicalDeviceFeatures::default()
+        .fill_mode_non_solid(supported.fill_mode_non_solid == vk::TRUE)
+}
+
 pub fn resize_swapchain(
     context: &mut InternalContext,
     mut pass: Option<&mut Pass>,
@@ -270,6 +442,7 @@ pub fn resize_swapchain(
         for fb in pass.framebuffers.drain(..) {
             unsafe { context.device.destroy_framebuffer(fb, None) };
         }
+        destroy_depth_target(&pass.depth, &context.device);
     }
 
     let desired_image_count = context.swapchain_image_views.len() as u32;
@@ -288,7 +461,7 @@ pub fn resize_swapchain(
         .surface(context.surface)
         .min_image_count(desired_image_count)
         .present_mode(context.present_mode)
-        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+        .image_usage(context.swapchain_usage)
         .image_extent(context.surface_resolution)
         .image_color_space(context.surface_format.color_space)
         .image_format(context.surface_format.format)
@@ -303,7 +476,7 @@ pub fn resize_swapchain(
             .create_swapchain(&create_info, None)
             .expect("Swapchain create error")
     };
-    let present_images = unsafe {
+    context.swapchain_images = unsafe {
         context
             .swapchain_loader
             .get_swapchain_images(context.swapchain)
@@ -311,18 +484,14 @@ pub fn resize_swapchain(
     };
     context.swapchain_image_views = create_swapchain_image_views(
         &context.device,
-        &present_images,
+        &context.swapchain_images,
         context.surface_format.format,
     );
+    name_swapchain_objects(context);
 
     if let Some(pass) = pass.as_mut() {
-        pass.framebuffers = create_framebuffers(
-            &context.device,
-            &context.swapchain_image_views,
-            pass.raw,
-            context.surface_resolution.width,
-            context.surface_resolution.height,
-        );
+        pass.depth = create_depth_target(context, pass.depth_format);
+        pass.framebuffers = create_framebuffers(context, pass.depth.view, pass.raw);
     }
     context.out_of_date = false;
 
@@ -361,44 +530,96 @@ unsafe fn pick_physical_device(
         .expect("No suitable physical device")
 }
 
-fn create_surface(entry: &ash::Entry, instance: &ash::Instance, window: &Window) -> vk::SurfaceKHR {
-    let create_info =
-        vk::Win32SurfaceCreateInfoKHR::default().hwnd(window.hwnd()).hinstance(window.hinstance());
-    let surface_fn = win32_surface::Instance::new(entry, instance);
-    unsafe { surface_fn.create_win32_surface(&create_info, None).expect("Surface create error") }
+pub fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_bits: u32,
+    flags: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+        .iter()
+        .enumerate()
+        .find(|(index, memory_type)| {
+            type_bits & (1 << index) != 0 && memory_type.property_flags.contains(flags)
+        })
+        .map(|(index, _)| index as u32)
 }
 
-#[allow(unused)]
-unsafe extern "system" fn vulkan_debug_callback(
-    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
-    _user_data: *mut std::os::raw::c_void,
-) -> vk::Bool32 {
-    let callback_data = *p_callback_data;
-
-    let message_id_name = if callback_data.p_message_id_name.is_null() {
-        Cow::from("")
-    } else {
-        CStr::from_ptr(callback_data.p_message_id_name).to_string_lossy()
-    };
+pub fn allocate_memory(
+    device: &ash::Device,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    requirements: vk::MemoryRequirements,
+    flags: vk::MemoryPropertyFlags,
+) -> Result<vk::DeviceMemory, VulkanError> {
+    let memory_type_index =
+        find_memory_type(memory_properties, requirements.memory_type_bits, flags)
+            .ok_or(VulkanError::ResourceCreationFailed)?;
+    let allocate_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type_index);
+    unsafe {
+        device
+            .allocate_memory(&allocate_info, None)
+            .map_err(|_| VulkanError::ResourceCreationFailed)
+    }
+}
 
-    let message = if callback_data.p_message.is_null() {
-        Cow::from("")
-    } else {
-        CStr::from_ptr(callback_data.p_message).to_string_lossy()
-    };
+/// Records and submits a one-off command buffer, blocking until the GPU has executed it
+pub fn immediate_submit(context: &InternalContext, record: impl FnOnce(vk::CommandBuffer)) {
+    let allocate_info = vk::CommandBufferAllocateInfo::default()
+        .command_pool(context.command_pool)
+        .command_buffer_count(1);
+    unsafe {
+        let command_buffer = context
+            .device
+            .allocate_command_buffers(&allocate_info)
+            .expect("Cant allocate command buffer")[0];
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        context
+            .device
+            .begin_command_buffer(command_buffer, &begin_info)
+            .expect("Command buffer begin failed");
+        record(command_buffer);
+        context.device.end_command_buffer(command_buffer).expect("End command buffer failed");
+
+        let fence = context
+            .device
+            .create_fence(&vk::FenceCreateInfo::default(), None)
+            .expect("Failed to create fence");
+        let command_buffers = [command_buffer];
+        let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+        context
+            .device
+            .queue_submit(context.present_queue, &[submit_info], fence)
+            .expect("Submit failed");
+        context.device.wait_for_fences(&[fence], true, u64::MAX).expect("Wait failed");
+        context.device.destroy_fence(fence, None);
+        context.device.free_command_buffers(context.command_pool, &command_buffers);
+    }
+}
 
-    match message_severity {
-        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => {
-            utils::error(format!("{message_id_name} : {message}"))
-        }
-        _ => utils::trace(format!("{message_id_name} : {message}")),
+fn create_descriptor_pool(device: &ash::Device) -> Result<vk::DescriptorPool, VulkanError> {
+    let pool_sizes = [
+        vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 256 },
+        vk::DescriptorPoolSize { ty: vk::DescriptorType::UNIFORM_BUFFER, descriptor_count: 256 },
+        vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 64 },
+    ];
+    let create_info = vk::DescriptorPoolCreateInfo::default().max_sets(256).pool_sizes(&pool_sizes);
+    unsafe {
+        device
+            .create_descriptor_pool(&create_info, None)
+            .map_err(|_| VulkanError::ResourceCreationFailed)
     }
+}
 
-    vk::FALSE
+fn create_surface(entry: &ash::Entry, instance: &ash::Instance, window: &Window) -> vk::SurfaceKHR {
+    let create_info =
+        vk::Win32SurfaceCreateInfoKHR::default().hwnd(window.hwnd()).hinstance(window.hinstance());
+    let surface_fn = win32_surface::Instance::new(entry, instance);
+    unsafe { surface_fn.create_win32_surface(&create_info, None).expect("Surface create error") }
 }
 
+#[allow(unused)]
 fn create_swapchain_image_views(
     device: &ash::Device,
     images: &[vk::Image],
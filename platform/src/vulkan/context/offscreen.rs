@@ -0,0 +1,180 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ash::{ext::debug_utils, khr::*, vk};
+
+use super::{
+    allocate_memory, create_debug_messenger, create_frames, create_gpu_profiler, create_instance,
+    create_swapchain_image_views, name_context_objects, pick_features, DebugConfig,
+    InternalContext, ValidationLog,
+};
+use crate::{utils, vulkan::VulkanError};
+
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+/// Render target used in place of the swapchain when there is no window
+pub struct OffscreenTarget {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+}
+
+/// Creates a context without a surface, rendering into a single offscreen image.
+/// Frames are submitted but never presented, which makes it usable on headless machines
+pub fn create_offscreen(
+    width: u32,
+    height: u32,
+    debug: DebugConfig,
+    validation_log: Arc<ValidationLog>,
+) -> Result<InternalContext, VulkanError> {
+    let (entry, instance, debug) = unsafe { create_instance(debug, false)? };
+
+    let (physical_device, queue_family_index) = unsafe { pick_offscreen_device(&instance)? };
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+    utils::trace(format!(
+        "Picked offscreen device: {:?}",
+        properties.device_name_as_c_str().unwrap_or_default()
+    ));
+
+    let priorities = [1.0];
+    let queue_create_infos = [vk::DeviceQueueCreateInfo::default()
+        .queue_family_index(queue_family_index)
+        .queue_priorities(&priorities)];
+    let device_features = unsafe { pick_features(&instance, physical_device) };
+    let create_info = vk::DeviceCreateInfo::default()
+        .enabled_features(&device_features)
+        .queue_create_infos(&queue_create_infos);
+
+    let device = unsafe {
+        instance.create_device(physical_device, &create_info, None).expect("Device create error")
+    };
+    let present_queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+
+    let extent = vk::Extent2D { width, height };
+    let usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC;
+    let target = create_offscreen_target(&device, &memory_properties, extent, usage)?;
+    let image_views = create_swapchain_image_views(&device, &[target.image], OFFSCREEN_FORMAT);
+
+    let create_info = vk::CommandPoolCreateInfo::default()
+        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+        .queue_family_index(queue_family_index);
+    let command_pool = unsafe {
+        device.create_command_pool(&create_info, None).expect("Create command pool failed")
+    };
+
+    let frames = create_frames(&device, command_pool);
+    let profiler = create_gpu_profiler(&instance, physical_device, queue_family_index, &device);
+    let debug_utils_loader = debug_utils::Instance::new(&entry, &instance);
+    let debug_messenger = create_debug_messenger(&debug_utils_loader, debug, &validation_log);
+    let debug_utils_device =
+        debug.debug_utils.then(|| debug_utils::Device::new(&instance, &device));
+
+    // Surface and swapchain loaders are never called for offscreen contexts
+    let surface_loader = surface::Instance::new(&entry, &instance);
+    let swapchain_loader = swapchain::Device::new(&instance, &device);
+
+    let context = InternalContext {
+        out_of_date: false,
+        minimized: false,
+        _entry: entry,
+        instance,
+        surface: vk::SurfaceKHR::null(),
+        surface_loader,
+        device,
+        physical_device,
+        memory_properties,
+        features: device_features,
+        present_queue,
+        pipeline_cache: vk::PipelineCache::null(),
+        swapchain_loader,
+        swapchain: vk::SwapchainKHR::null(),
+        swapchain_usage: usage,
+        swapchain_images: vec![target.image],
+        command_pool,
+        swapchain_image_views: image_views,
+        target_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        offscreen: Some(target),
+        surface_format: vk::SurfaceFormatKHR {
+            format: OFFSCREEN_FORMAT,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        },
+        surface_resolution: extent,
+        present_mode: vk::PresentModeKHR::FIFO,
+        debug,
+        debug_utils_loader,
+        debug_messenger,
+        _validation_log: validation_log,
+        debug_utils_device,
+        current_frame: 0,
+        present_index: None,
+        target_drawn: false,
+        fence_wait: Duration::ZERO,
+        frames,
+        readback: None,
+        captured: None,
+        profiler,
+    };
+    name_context_objects(&context);
+    Ok(context)
+}
+
+pub fn destroy_offscreen_target(target: &OffscreenTarget, device: &ash::Device) {
+    unsafe {
+        device.destroy_image(target.image, None);
+        device.free_memory(target.memory, None);
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn create_offscreen_target(
+    device: &ash::Device,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    extent: vk::Extent2D,
+    usage: vk::ImageUsageFlags,
+) -> Result<OffscreenTarget, VulkanError> {
+    let create_info = vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(OFFSCREEN_FORMAT)
+        .extent(vk::Extent3D { width: extent.width, height: extent.height, depth: 1 })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED);
+    unsafe {
+        let image = device
+            .create_image(&create_info, None)
+            .map_err(|_| VulkanError::ResourceCreationFailed)?;
+        let requirements = device.get_image_memory_requirements(image);
+        let memory = allocate_memory(
+            device,
+            memory_properties,
+            requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .inspect_err(|_| device.destroy_image(image, None))?;
+        device.bind_image_memory(image, memory, 0).expect("Bind image memory failed");
+        Ok(OffscreenTarget { image, memory })
+    }
+}
+
+unsafe fn pick_offscreen_device(
+    instance: &ash::Instance,
+) -> Result<(vk::PhysicalDevice, u32), VulkanError> {
+    let all_devices =
+        instance.enumerate_physical_devices().expect("Enumerate physical devices error");
+    all_devices
+        .iter()
+        .find_map(|device| {
+            instance
+                .get_physical_device_queue_family_properties(*device)
+                .iter()
+                .position(|info| info.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+                .map(|index| (*device, index as u32))
+        })
+        .ok_or(VulkanError::VulkanNotSupported)
+}
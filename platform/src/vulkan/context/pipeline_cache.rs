@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use ash::vk;
+
+use super::InternalContext;
+use crate::utils;
+
+const CACHE_MAGIC: [u8; 4] = *b"VKPC";
+const CACHE_VERSION: u32 = 1;
+/// Magic, version, vendor id, device id, driver version, cache UUID and data length
+const HEADER_SIZE: usize = 4 + 4 + 4 + 4 + 4 + vk::UUID_SIZE + 8;
+/// Size of `VkPipelineCacheHeaderVersionOne`
+const VULKAN_HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// Creates the pipeline cache used for every pipeline, filled from `path` if the file
+/// was written by the same device and driver. Anything else is discarded
+pub fn load_pipeline_cache(context: &InternalContext, path: Option<&Path>) -> vk::PipelineCache {
+    let properties =
+        unsafe { context.instance.get_physical_device_properties(context.physical_device) };
+    let data = path.and_then(|path| match std::fs::read(path) {
+        Ok(file) => {
+            let data = validate_cache(&file, &properties);
+            if data.is_none() {
+                utils::trace(format!("Discarding stale pipeline cache {}", path.display()));
+            }
+            data.map(<[u8]>::to_vec)
+        }
+        Err(_) => None,
+    });
+
+    if let Some(data) = data {
+        let create_info = vk::PipelineCacheCreateInfo::default().initial_data(&data);
+        match unsafe { context.device.create_pipeline_cache(&create_info, None) } {
+            Ok(cache) => {
+                utils::trace(format!("Loaded pipeline cache {}b", data.len()));
+                return cache;
+            }
+            Err(e) => utils::error(format!("Pipeline cache rejected by the driver: {}", e)),
+        }
+    }
+    let create_info = vk::PipelineCacheCreateInfo::default();
+    unsafe {
+        context
+            .device
+            .create_pipeline_cache(&create_info, None)
+            .expect("Cant create pipeline cache")
+    }
+}
+
+/// Writes the cache next to `path` first and renames it, so a crash never leaves half a file
+pub fn save_pipeline_cache(context: &InternalContext, path: &Path) {
+    if context.pipeline_cache == vk::PipelineCache::null() {
+        return;
+    }
+    let data = match unsafe { context.device.get_pipeline_cache_data(context.pipeline_cache) } {
+        Ok(data) => data,
+        Err(e) => {
+            utils::error(format!("Cant read pipeline cache: {}", e));
+            return;
+        }
+    };
+    let properties =
+        unsafe { context.instance.get_physical_device_properties(context.physical_device) };
+    let file = encode_cache(&data, &properties);
+
+    let temp_path = path.with_extension("tmp");
+    let result = std::fs::write(&temp_path, &file).and_then(|_| std::fs::rename(&temp_path, path));
+    match result {
+        Ok(()) => utils::trace(format!("Saved pipeline cache {}b", data.len())),
+        Err(e) => utils::error(format!("Cant write pipeline cache {}: {}", path.display(), e)),
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+/// Prefixes the Vulkan cache data with our header
+fn encode_cache(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+    let mut file = Vec::with_capacity(HEADER_SIZE + data.len());
+    file.extend_from_slice(&CACHE_MAGIC);
+    file.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+    file.extend_from_slice(&properties.vendor_id.to_le_bytes());
+    file.extend_from_slice(&properties.device_id.to_le_bytes());
+    file.extend_from_slice(&properties.driver_version.to_le_bytes());
+    file.extend_from_slice(&properties.pipeline_cache_uuid);
+    file.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    file.extend_from_slice(data);
+    file
+}
+
+/// Returns the Vulkan cache data if both our header and the Vulkan header match the device
+fn validate_cache<'a>(
+    file: &'a [u8],
+    properties: &vk::PhysicalDeviceProperties,
+) -> Option<&'a [u8]> {
+    let read_u32 = |bytes: &[u8], offset: usize| {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    };
+    if file.len() < HEADER_SIZE || file[0..4] != CACHE_MAGIC {
+        return None;
+    }
+    let uuid_end = 20 + vk::UUID_SIZE;
+    let data_len = u64::from_le_bytes(file[uuid_end..uuid_end + 8].try_into().unwrap());
+    let data = &file[HEADER_SIZE..];
+    if read_u32(file, 4) != CACHE_VERSION
+        || read_u32(file, 8) != properties.vendor_id
+        || read_u32(file, 12) != properties.device_id
+        || read_u32(file, 16) != properties.driver_version
+        || file[20..uuid_end] != properties.pipeline_cache_uuid
+        || data_len != data.len() as u64
+    {
+        return None;
+    }
+
+    // The driver checks this too, but some drivers are known to crash on foreign data
+    if data.len() < VULKAN_HEADER_SIZE
+        || (read_u32(data, 0) as usize) < VULKAN_HEADER_SIZE
+        || read_u32(data, 0) as usize > data.len()
+        || read_u32(data, 4) != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        || read_u32(data, 8) != properties.vendor_id
+        || read_u32(data, 12) != properties.device_id
+        || data[16..VULKAN_HEADER_SIZE] != properties.pipeline_cache_uuid
+    {
+        return None;
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x2684,
+            driver_version: 7,
+            pipeline_cache_uuid: [0xab; vk::UUID_SIZE],
+            ..Default::default()
+        }
+    }
+
+    /// Vulkan cache data as the driver would return it, with a few bytes of payload
+    fn cache_data(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(VULKAN_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(
+            &(vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_le_bytes(),
+        );
+        data.extend_from_slice(&properties.vendor_id.to_le_bytes());
+        data.extend_from_slice(&properties.device_id.to_le_bytes());
+        data.extend_from_slice(&properties.pipeline_cache_uuid);
+        data.extend_from_slice(&[1, 2, 3, 4]);
+        data
+    }
+
+    fn cache_file() -> Vec<u8> {
+        encode_cache(&cache_data(&properties()), &properties())
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = cache_data(&properties());
+        let file = encode_cache(&data, &properties());
+        assert_eq!(validate_cache(&file, &properties()), Some(&data[..]));
+    }
+
+    #[test]
+    fn rejects_wrong_magic() {
+        let mut file = cache_file();
+        file[0] = b'X';
+        assert_eq!(validate_cache(&file, &properties()), None);
+    }
+
+    #[test]
+    fn rejects_wrong_version() {
+        let mut file = cache_file();
+        file[4..8].copy_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+        assert_eq!(validate_cache(&file, &properties()), None);
+    }
+
+    #[test]
+    fn rejects_other_device_or_driver() {
+        let file = cache_file();
+        let other_vendor = vk::PhysicalDeviceProperties { vendor_id: 0x1002, ..properties() };
+        let other_device = vk::PhysicalDeviceProperties { device_id: 0x2685, ..properties() };
+        let other_driver = vk::PhysicalDeviceProperties { driver_version: 8, ..properties() };
+        assert_eq!(validate_cache(&file, &other_vendor), None);
+        assert_eq!(validate_cache(&file, &other_device), None);
+        assert_eq!(validate_cache(&file, &other_driver), None);
+    }
+
+    #[test]
+    fn rejects_wrong_uuid() {
+        let mut other_uuid = properties();
+        other_uuid.pipeline_cache_uuid[vk::UUID_SIZE - 1] ^= 1;
+        assert_eq!(validate_cache(&cache_file(), &other_uuid), None);
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let file = cache_file();
+        assert_eq!(validate_cache(&file[..HEADER_SIZE - 1], &properties()), None);
+        assert_eq!(validate_cache(&file[..file.len() - 1], &properties()), None);
+        assert_eq!(validate_cache(&[], &properties()), None);
+    }
+
+    #[test]
+    fn rejects_data_len_mismatch() {
+        let mut file = cache_file();
+        file.push(0);
+        assert_eq!(validate_cache(&file, &properties()), None);
+    }
+
+    #[test]
+    fn rejects_bad_vulkan_header() {
+        let properties = properties();
+        let mut too_long = cache_data(&properties);
+        let len = too_long.len() as u32 + 1;
+        too_long[0..4].copy_from_slice(&len.to_le_bytes());
+        let mut too_short = cache_data(&properties);
+        too_short[0..4].copy_from_slice(&(VULKAN_HEADER_SIZE as u32 - 1).to_le_bytes());
+        let mut other_vendor = cache_data(&properties);
+        other_vendor[8..12].copy_from_slice(&0x1002u32.to_le_bytes());
+
+        for data in [too_long, too_short, other_vendor] {
+            assert_eq!(validate_cache(&encode_cache(&data, &properties), &properties), None);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+use std::ffi::{c_char, c_void, CStr};
+use std::fmt;
+use std::sync::Mutex;
+
+use ash::vk;
+
+use crate::{utils, vulkan::constants::MAX_VALIDATION_MESSAGES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValidationSeverity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidationKind {
+    General,
+    Validation,
+    Performance,
+}
+
+/// Debug facilities of a context, each one can be enabled on its own.
+/// Ones the driver doesn't provide are reported in the log and skipped
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DebugConfig {
+    /// Enables `VK_LAYER_KHRONOS_validation`
+    pub validation: bool,
+    /// Enables `VK_EXT_debug_utils`, needed for object names, labels and the messenger
+    pub debug_utils: bool,
+    /// Lowest severity the messenger reports, `None` creates no messenger
+    pub min_severity: Option<ValidationSeverity>,
+}
+
+impl DebugConfig {
+    /// Same as `default()`
+    pub const NONE: Self = Self { validation: false, debug_utils: false, min_severity: None };
+    pub const VALIDATION: Self =
+        Self { validation: true, debug_utils: true, min_severity: Some(ValidationSeverity::Info) };
+}
+
+impl ValidationSeverity {
+    /// Flags of this severity and every more severe one
+    pub fn and_above(self) -> vk::DebugUtilsMessageSeverityFlagsEXT {
+        let all = [
+            (ValidationSeverity::Verbose, vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE),
+            (ValidationSeverity::Info, vk::DebugUtilsMessageSeverityFlagsEXT::INFO),
+            (ValidationSeverity::Warning, vk::DebugUtilsMessageSeverityFlagsEXT::WARNING),
+            (ValidationSeverity::Error, vk::DebugUtilsMessageSeverityFlagsEXT::ERROR),
+        ];
+        all.into_iter()
+            .filter(|(severity, _)| *severity >= self)
+            .fold(vk::DebugUtilsMessageSeverityFlagsEXT::empty(), |flags, (_, flag)| flags | flag)
+    }
+}
+
+/// One message of the debug messenger
+#[derive(Debug, Clone)]
+pub struct ValidationMessage {
+    pub severity: ValidationSeverity,
+    pub kind: ValidationKind,
+    /// VUID of the message, e.g. `VUID-vkCmdDraw-None-08600`
+    pub message_id_name: String,
+    pub message_id: i32,
+    pub message: String,
+    /// Objects the message is about, with their debug names when they have one
+    pub objects: Vec<String>,
+}
+
+/// Messages collected by the debug messenger callback, shared with `VulkanContext`.
+/// Keeps the latest `MAX_VALIDATION_MESSAGES` messages and the first error
+#[derive(Default)]
+pub struct ValidationLog {
+    messages: Mutex<VecDeque<ValidationMessage>>,
+    first_error: Mutex<Option<ValidationMessage>>,
+}
+
+impl ValidationLog {
+    pub fn push(&self, message: ValidationMessage) {
+        if message.severity == ValidationSeverity::Error {
+            let mut first_error = self.first_error.lock().unwrap();
+            if first_error.is_none() {
+                *first_error = Some(message.clone());
+            }
+        }
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() == MAX_VALIDATION_MESSAGES {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+    }
+
+    pub fn messages(&self) -> Vec<ValidationMessage> {
+        self.messages.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Empties the log, including the first error
+    pub fn take(&self) -> Vec<ValidationMessage> {
+        self.first_error.lock().unwrap().take();
+        self.messages.lock().unwrap().drain(..).collect()
+    }
+
+    pub fn first_error(&self) -> Option<ValidationMessage> {
+        self.first_error.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Display for ValidationMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:?} {} ({:#x}): {}",
+            self.severity, self.kind, self.message_id_name, self.message_id, self.message
+        )?;
+        if !self.objects.is_empty() {
+            write!(f, " [{}]", self.objects.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// `user_data` is the `ValidationLog` of the context, which outlives the messenger
+pub unsafe extern "system" fn vulkan_debug_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    user_data: *mut c_void,
+) -> vk::Bool32 {
+    let callback_data = *p_callback_data;
+    let objects = if callback_data.p_objects.is_null() {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts(callback_data.p_objects, callback_data.object_count as usize)
+    };
+    let message = ValidationMessage {
+        severity: severity(message_severity),
+        kind: kind(message_type),
+        message_id_name: c_string(callback_data.p_message_id_name),
+        message_id: callback_data.message_id_number,
+        message: c_string(callback_data.p_message),
+        objects: objects.iter().map(|object| describe_object(object)).collect(),
+    };
+
+    let text = format!("{} : {}", message.message_id_name, message.message);
+    match message.severity {
+        ValidationSeverity::Error => utils::error(text),
+        _ => utils::trace(text),
+    }
+    if let Some(log) = (user_data as *const ValidationLog).as_ref() {
+        log.push(message);
+    }
+
+    vk::FALSE
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn severity(flags: vk::DebugUtilsMessageSeverityFlagsEXT) -> ValidationSeverity {
+    if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        ValidationSeverity::Error
+    } else if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        ValidationSeverity::Warning
+    } else if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        ValidationSeverity::Info
+    } else {
+        ValidationSeverity::Verbose
+    }
+}
+
+fn kind(flags: vk::DebugUtilsMessageTypeFlagsEXT) -> ValidationKind {
+    if flags.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION) {
+        ValidationKind::Validation
+    } else if flags.contains(vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE) {
+        ValidationKind::Performance
+    } else {
+        ValidationKind::General
+    }
+}
+
+unsafe fn c_string(pointer: *const c_char) -> String {
+    if pointer.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(pointer).to_string_lossy().into_owned()
+    }
+}
+
+unsafe fn describe_object(object: &vk::DebugUtilsObjectNameInfoEXT) -> String {
+    let name = c_string(object.p_object_name);
+    if name.is_empty() {
+        format!("{:?} {:#x}", object.object_type, object.object_handle)
+    } else {
+        format!("{} ({:?} {:#x})", name, object.object_type, object.object_handle)
+    }
+}
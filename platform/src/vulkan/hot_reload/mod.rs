@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use shaderc::{Compiler, ShaderKind};
+
+use super::PipelineHandle;
+use crate::utils;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches the GLSL sources of pipelines and recompiles them when they change on disk
+pub struct ShaderReloader {
+    directory: PathBuf,
+    compiler: Compiler,
+    watched: Vec<WatchedPipeline>,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+}
+
+/// New SPIR-V for a watched pipeline
+pub enum Reload {
+    Graphics { pipeline: PipelineHandle, vertex: Vec<u8>, fragment: Vec<u8> },
+    Compute { pipeline: PipelineHandle, shader: Vec<u8> },
+}
+
+enum WatchedPipeline {
+    Graphics { pipeline: PipelineHandle, vertex: PathBuf, fragment: PathBuf },
+    Compute { pipeline: PipelineHandle, shader: PathBuf },
+}
+
+impl ShaderReloader {
+    pub fn new(directory: PathBuf) -> Option<Self> {
+        let Some(compiler) = Compiler::new() else {
+            utils::error("Cant create shader compiler, hot reload is disabled");
+            return None;
+        };
+        utils::trace(format!("Watching shaders in {}", directory.display()));
+        Some(Self {
+            directory,
+            compiler,
+            watched: vec![],
+            modified: HashMap::new(),
+            last_poll: Instant::now(),
+        })
+    }
+
+    pub fn watch_graphics(&mut self, pipeline: PipelineHandle, vertex: &str, fragment: &str) {
+        let vertex = self.directory.join(vertex);
+        let fragment = self.directory.join(fragment);
+        self.track(&vertex);
+        self.track(&fragment);
+        self.watched.push(WatchedPipeline::Graphics { pipeline, vertex, fragment });
+    }
+
+    pub fn watch_compute(&mut self, pipeline: PipelineHandle, shader: &str) {
+        let shader = self.directory.join(shader);
+        self.track(&shader);
+        self.watched.push(WatchedPipeline::Compute { pipeline, shader });
+    }
+
+    /// Recompiles the pipelines whose sources changed since the last poll.
+    /// Sources that fail to compile are logged and skipped until they change again
+    pub fn poll(&mut self) -> Vec<Reload> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = vec![];
+        for (path, time) in self.modified.iter_mut() {
+            if let Some(modified) = modified_time(path) {
+                if modified != *time {
+                    *time = modified;
+                    changed.push(path.clone());
+                }
+            }
+        }
+        if changed.is_empty() {
+            return vec![];
+        }
+
+        let mut reloads = vec![];
+        for watched in self.watched.iter() {
+            match watched {
+                WatchedPipeline::Graphics { pipeline, vertex, fragment } => {
+                    if !changed.contains(vertex) && !changed.contains(fragment) {
+                        continue;
+                    }
+                    let (Some(vertex), Some(fragment)) =
+                        (self.compile(vertex), self.compile(fragment))
+                    else {
+                        continue;
+                    };
+                    reloads.push(Reload::Graphics { pipeline: *pipeline, vertex, fragment });
+                }
+                WatchedPipeline::Compute { pipeline, shader } => {
+                    if !changed.contains(shader) {
+                        continue;
+                    }
+                    if let Some(shader) = self.compile(shader) {
+                        reloads.push(Reload::Compute { pipeline: *pipeline, shader });
+                    }
+                }
+            }
+        }
+        reloads
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+impl ShaderReloader {
+    fn track(&mut self, path: &Path) {
+        let modified = modified_time(path).unwrap_or(SystemTime::UNIX_EPOCH);
+        self.modified.entry(path.to_path_buf()).or_insert(modified);
+    }
+
+    fn compile(&self, path: &Path) -> Option<Vec<u8>> {
+        let kind = match path.extension().and_then(OsStr::to_str) {
+            Some("vert") => ShaderKind::Vertex,
+            Some("frag") => ShaderKind::Fragment,
+            Some("comp") => ShaderKind::Compute,
+            _ => {
+                utils::error(format!("Unknown shader stage of {}", path.display()));
+                return None;
+            }
+        };
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                utils::error(format!("Cant read {}: {}", path.display(), e));
+                return None;
+            }
+        };
+        let name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+        match self.compiler.compile_into_spirv(&source, kind, name, "main", None) {
+            Ok(artifact) => {
+                utils::trace(format!("Recompiled {}", path.display()));
+                Some(artifact.as_binary_u8().to_vec())
+            }
+            Err(e) => {
+                utils::error(format!("Shader compilation failed:\n{}", e));
+                None
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
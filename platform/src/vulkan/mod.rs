@@ -1,46 +1,140 @@
 use ash::vk;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::utils;
 use crate::window::{Window, WindowEvent};
 
-pub use resources::PipelineConfig;
+pub use capture::CapturedImage;
+pub use context::validation::{DebugConfig, ValidationKind, ValidationMessage, ValidationSeverity};
+pub use profiler::{ScopeHistory, ScopeTiming};
+pub use resources::{
+    field_format, Binding, BindingType, BlendComponent, BlendFactor, BlendOp, BlendState,
+    BufferConfig, BufferHandle, BufferUsage, ColorAttachmentState, ColorWrites, CompareOp,
+    ComputePipelineConfig, CullMode, DepthState, DynamicState, FrontFace, PipelineConfig,
+    PipelineHandle, PipelineState, PolygonMode, StencilFace, StencilOp, StencilState,
+    TextureConfig, TextureFormat, TextureHandle, Topology, Vertex, VertexAttribute,
+    VertexAttributeType, VertexFormat, VertexLayout, VertexRate,
+};
 
+use capture::*;
 use constants::*;
+use context::debug::*;
+use context::offscreen::*;
+use context::pipeline_cache::*;
+use context::validation::ValidationLog;
 use context::*;
+#[cfg(feature = "hot-reload")]
+use hot_reload::*;
 use pass::*;
+use profiler::*;
 use resources::*;
 
+mod capture;
 mod constants;
 mod context;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
 mod pass;
+mod profiler;
 mod resources;
 
 pub struct VulkanContext {
     pub width: u32,
     pub height: u32,
-    pub validation: bool,
+    /// Requested debug facilities, the ones the driver lacks are skipped
+    pub debug: DebugConfig,
+    pub offscreen: bool,
+    /// Pipeline cache file, loaded when the context is created and written back on drop
+    pub pipeline_cache_path: Option<PathBuf>,
+    /// Panic in `end_frame` once the validation layer reported an error, meant for tests
+    pub panic_on_validation_error: bool,
+    pub(crate) validation_log: Arc<ValidationLog>,
     pub(crate) internal: Option<InternalContext>,
     pub(crate) resources: Option<Resources>,
     pub(crate) pass: Option<Pass>,
+    #[cfg(feature = "hot-reload")]
+    pub(crate) shader_reloader: Option<ShaderReloader>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum VulkanError {
     WindowNotInitialized,
-    ValidationNotPresent,
+    VulkanNotSupported,
     ResourceCreationFailed,
+    PipelineNotFound,
+    BufferNotFound,
+    TextureNotFound,
+    BufferNotHostVisible,
+    BufferOutOfRange,
+    InvalidBinding,
+    WrongPipelineKind,
+    InsideRenderPass,
+    CaptureNotSupported,
+    NoFrameInProgress,
+    /// `capture_frame` was called in a frame without a draw pass
+    TargetNotDrawn,
+    /// SPIR-V that could not be parsed or uses unsupported resources
+    InvalidShader(String),
+    /// The device lacks an optional feature the pipeline state needs
+    FeatureNotSupported(&'static str),
+    /// Shader stages disagree with each other or with the pipeline they are used in
+    PipelineLayoutMismatch(String),
+}
+
+/// Execution and memory dependency between compute and graphics work in the same frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Barrier {
+    /// Compute writes read by a following dispatch
+    ComputeToCompute,
+    /// Compute writes consumed as vertex, index or indirect arguments
+    ComputeToVertexInput,
+    /// Compute writes read by vertex or fragment shaders
+    ComputeToGraphics,
+    /// Graphics writes read by a following dispatch
+    GraphicsToCompute,
+    /// Compute writes read on the CPU with `read_buffer` once the frame completes
+    ComputeToHost,
 }
 
 impl VulkanContext {
-    pub fn new(width: u32, height: u32, validation: bool) -> Self {
-        Self { width, height, validation, internal: None, resources: None, pass: None }
+    pub fn new(width: u32, height: u32, debug: DebugConfig) -> Self {
+        Self {
+            width,
+            height,
+            debug,
+            offscreen: false,
+            pipeline_cache_path: Some(PathBuf::from(PIPELINE_CACHE_FILE)),
+            panic_on_validation_error: false,
+            validation_log: Arc::default(),
+            internal: None,
+            resources: None,
+            pass: None,
+            #[cfg(feature = "hot-reload")]
+            shader_reloader: None,
+        }
+    }
+
+    /// Context that renders into an image instead of a window, see `create_offscreen_context`
+    pub fn new_offscreen(width: u32, height: u32, debug: DebugConfig) -> Self {
+        let mut context = Self::new(width, height, debug);
+        context.offscreen = true;
+        context.pipeline_cache_path = None;
+        context
     }
 }
 
 pub fn update_context(context: &mut VulkanContext, window: &Window) {
+    if context.offscreen {
+        return;
+    }
     if let Some(internal) = context.internal.as_mut() {
+        internal.minimized = window.minimized;
         if window.internal.destroyed {
-            context.internal = None;
+            destroy_device_objects(context);
+        } else if internal.minimized {
+            // The swapchain is recreated by the `Resize` that comes with restoring
         } else if window.events.contains(&WindowEvent::Resize) || internal.out_of_date {
             resize_swapchain(
                 internal,
@@ -50,8 +144,10 @@ pub fn update_context(context: &mut VulkanContext, window: &Window) {
             );
         }
     } else if window.internal.initialized {
-        match create_context(window, context.validation) {
-            Ok(internal) => {
+        match create_context(window, context.debug, context.validation_log.clone()) {
+            Ok(mut internal) => {
+                internal.pipeline_cache =
+                    load_pipeline_cache(&internal, context.pipeline_cache_path.as_deref());
                 context.internal = Some(internal);
                 context.resources = Some(create_resources());
             }
@@ -60,6 +156,22 @@ pub fn update_context(context: &mut VulkanContext, window: &Window) {
     }
 }
 
+pub fn create_offscreen_context(context: &mut VulkanContext) -> Result<(), VulkanError> {
+    if context.internal.is_none() {
+        let mut internal = create_offscreen(
+            context.width,
+            context.height,
+            context.debug,
+            context.validation_log.clone(),
+        )?;
+        internal.pipeline_cache =
+            load_pipeline_cache(&internal, context.pipeline_cache_path.as_deref());
+        context.internal = Some(internal);
+        context.resources = Some(create_resources());
+    }
+    Ok(())
+}
+
 pub fn update_pass(context: &mut VulkanContext) {
     if let Some(internal) = context.internal.as_mut() {
         if context.pass.is_none() {
@@ -73,27 +185,44 @@ pub fn update_pass(context: &mut VulkanContext) {
 
 pub fn begin_frame(context: &mut VulkanContext) {
     if let Some(internal) = context.internal.as_mut() {
-        if internal.out_of_date || internal.present_index.is_some() {
+        if internal.minimized && internal.present_index.is_none() {
+            if let Some(resources) = context.resources.as_mut() {
+                release_completed_frames(internal, resources);
+            }
+        }
+        if internal.minimized || internal.out_of_date || internal.present_index.is_some() {
             return;
         }
         unsafe {
             let frame = &internal.frames[internal.current_frame];
 
+            let wait_start = Instant::now();
             internal
                 .device
                 .wait_for_fences(&[frame.reuse_fence], true, u64::MAX)
                 .expect("Wait failed");
+            internal.fence_wait = wait_start.elapsed();
             internal.device.reset_fences(&[frame.reuse_fence]).expect("Reset failed");
+            if let Some(resources) = context.resources.as_mut() {
+                resources.flush_deletion_queue(internal, internal.current_frame);
+            }
+            reset_descriptor_pools(internal, internal.current_frame);
+            let frame = &internal.frames[internal.current_frame];
 
-            let (present_index, _) = internal
-                .swapchain_loader
-                .acquire_next_image(
-                    internal.swapchain,
-                    u64::MAX,
-                    frame.presentation_complete_semaphore,
-                    vk::Fence::null(),
-                )
-                .expect("Acquire image failed");
+            let present_index = if internal.offscreen.is_some() {
+                0
+            } else {
+                let (present_index, _) = internal
+                    .swapchain_loader
+                    .acquire_next_image(
+                        internal.swapchain,
+                        u64::MAX,
+                        frame.presentation_complete_semaphore,
+                        vk::Fence::null(),
+                    )
+                    .expect("Acquire image failed");
+                present_index
+            };
 
             internal
                 .device
@@ -108,22 +237,585 @@ pub fn begin_frame(context: &mut VulkanContext) {
                 .device
                 .begin_command_buffer(frame.command_buffer, &begin_info)
                 .expect("Command buffer begin failed");
+            if let Some(profiler) = internal.profiler.as_mut() {
+                profiler.begin_frame(
+                    &internal.device,
+                    internal.current_frame,
+                    frame.command_buffer,
+                );
+            }
 
             internal.present_index = Some(present_index);
+            internal.target_drawn = false;
         };
     }
 }
 
-pub fn draw_pass(context: &mut VulkanContext) {
+pub fn begin_draw_pass(context: &mut VulkanContext) {
     if let (Some(internal), Some(pass)) = (context.internal.as_mut(), context.pass.as_mut()) {
         if let Some(present_index) = internal.present_index {
-            begin_pass(internal, pass, present_index);
-            end_pass(internal);
+            if !pass.active {
+                begin_scope(internal, "Draw pass");
+                begin_pass(internal, pass, present_index);
+            }
+        }
+    }
+}
+
+pub fn end_draw_pass(context: &mut VulkanContext) {
+    if let (Some(internal), Some(pass)) = (context.internal.as_mut(), context.pass.as_mut()) {
+        if pass.active {
+            end_pass(internal, pass);
+            end_scope(internal);
+            internal.target_drawn = true;
         }
     }
 }
 
+/// Records a non-indexed draw, only valid between `begin_draw_pass` and `end_draw_pass`.
+/// `bindings` and `push_constants` are given as for `dispatch`
+pub fn draw(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+    bindings: &[Binding],
+    push_constants: &[u8],
+    vertex_count: u32,
+    instance_count: u32,
+) -> Result<(), VulkanError> {
+    let (Some(internal), Some(resources), Some(pass)) =
+        (context.internal.as_mut(), context.resources.as_ref(), context.pass.as_ref())
+    else {
+        return Err(VulkanError::NoFrameInProgress);
+    };
+    if !pass.active {
+        return Err(VulkanError::NoFrameInProgress);
+    }
+    let pipeline = resources.get_pipeline(pipeline)?;
+    if pipeline.bind_point != vk::PipelineBindPoint::GRAPHICS {
+        return Err(VulkanError::WrongPipelineKind);
+    }
+    resources.bind_pipeline(internal, pipeline, bindings, push_constants)?;
+    let command_buffer = internal.frames[internal.current_frame].command_buffer;
+    unsafe {
+        internal.device.cmd_draw(command_buffer, vertex_count, instance_count, 0, 0);
+    }
+    Ok(())
+}
+
+/// Draws with arguments read from `buffer` as `VkDrawIndirectCommand`s, e.g. written by culling
+pub fn draw_indirect(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+    bindings: &[Binding],
+    push_constants: &[u8],
+    buffer: BufferHandle,
+    offset: u64,
+    draw_count: u32,
+) -> Result<(), VulkanError> {
+    let (Some(internal), Some(resources), Some(pass)) =
+        (context.internal.as_mut(), context.resources.as_ref(), context.pass.as_ref())
+    else {
+        return Err(VulkanError::NoFrameInProgress);
+    };
+    if !pass.active {
+        return Err(VulkanError::NoFrameInProgress);
+    }
+    let pipeline = resources.get_pipeline(pipeline)?;
+    if pipeline.bind_point != vk::PipelineBindPoint::GRAPHICS {
+        return Err(VulkanError::WrongPipelineKind);
+    }
+    let buffer = resources.get_buffer(buffer)?;
+    resources.bind_pipeline(internal, pipeline, bindings, push_constants)?;
+    let command_buffer = internal.frames[internal.current_frame].command_buffer;
+    unsafe {
+        internal.device.cmd_draw_indirect(
+            command_buffer,
+            buffer.raw,
+            offset,
+            draw_count,
+            std::mem::size_of::<vk::DrawIndirectCommand>() as u32,
+        );
+    }
+    Ok(())
+}
+
+/// Binds `buffers[i]` to vertex buffer binding `i` for the following draws
+pub fn bind_vertex_buffers(
+    context: &mut VulkanContext,
+    buffers: &[BufferHandle],
+) -> Result<(), VulkanError> {
+    let (Some(internal), Some(resources)) = (context.internal.as_ref(), context.resources.as_ref())
+    else {
+        return Err(VulkanError::NoFrameInProgress);
+    };
+    if internal.present_index.is_none() {
+        return Err(VulkanError::NoFrameInProgress);
+    }
+    let raw_buffers = buffers
+        .iter()
+        .map(|buffer| resources.get_buffer(*buffer).map(|buffer| buffer.raw))
+        .collect::<Result<Vec<_>, _>>()?;
+    let offsets = vec![0; raw_buffers.len()];
+    let command_buffer = internal.frames[internal.current_frame].command_buffer;
+    unsafe {
+        internal.device.cmd_bind_vertex_buffers(command_buffer, 0, &raw_buffers, &offsets);
+    }
+    Ok(())
+}
+
+/// Sets `DynamicState::BlendConstants` for the following draws
+pub fn set_blend_constants(context: &mut VulkanContext, constants: [f32; 4]) {
+    if let Some((internal, command_buffer)) = recording_frame(context) {
+        unsafe { internal.device.cmd_set_blend_constants(command_buffer, &constants) };
+    }
+}
+
+/// Sets `DynamicState::StencilReference` of both faces for the following draws
+pub fn set_stencil_reference(context: &mut VulkanContext, reference: u32) {
+    if let Some((internal, command_buffer)) = recording_frame(context) {
+        unsafe {
+            internal.device.cmd_set_stencil_reference(
+                command_buffer,
+                vk::StencilFaceFlags::FRONT_AND_BACK,
+                reference,
+            )
+        };
+    }
+}
+
+/// Sets `DynamicState::DepthBias` for the following draws
+pub fn set_depth_bias(context: &mut VulkanContext, constant: f32, slope: f32) {
+    if let Some((internal, command_buffer)) = recording_frame(context) {
+        unsafe { internal.device.cmd_set_depth_bias(command_buffer, constant, 0.0, slope) };
+    }
+}
+
+/// Opens a labeled region of the current frame, shown by RenderDoc and in validation
+/// messages. Close it with `pop_debug_label`. Only recorded with `DebugConfig::debug_utils`
+pub fn push_debug_label(context: &mut VulkanContext, name: &str, color: [f32; 4]) {
+    if let Some((internal, command_buffer)) = recording_frame(context) {
+        begin_label(internal, command_buffer, name, color);
+    }
+}
+
+pub fn pop_debug_label(context: &mut VulkanContext) {
+    if let Some((internal, command_buffer)) = recording_frame(context) {
+        end_label(internal, command_buffer);
+    }
+}
+
+/// Marks a single point of the current frame, e.g. right before a draw
+pub fn insert_debug_label(context: &mut VulkanContext, name: &str, color: [f32; 4]) {
+    if let Some((internal, command_buffer)) = recording_frame(context) {
+        insert_label(internal, command_buffer, name, color);
+    }
+}
+
+/// Starts timing a scope of the current frame on the GPU, closed by `end_gpu_scope`.
+/// Scopes can be nested, the draw pass and the whole frame are timed on their own
+pub fn begin_gpu_scope(context: &mut VulkanContext, name: &str) {
+    if let Some(internal) = context.internal.as_mut() {
+        if internal.present_index.is_some() {
+            begin_scope(internal, name);
+        }
+    }
+}
+
+pub fn end_gpu_scope(context: &mut VulkanContext) {
+    if let Some(internal) = context.internal.as_mut() {
+        if internal.present_index.is_some() {
+            end_scope(internal);
+        }
+    }
+}
+
+/// GPU scopes of the latest resolved frame, which lags `FRAMES_IN_FLIGHT` frames behind
+pub fn gpu_timings(context: &VulkanContext) -> &[ScopeTiming] {
+    match context.internal.as_ref().and_then(|internal| internal.profiler.as_ref()) {
+        Some(profiler) => profiler.timings(),
+        None => &[],
+    }
+}
+
+/// Recent timings of every GPU scope name, e.g. to display or log them with `Display`
+pub fn gpu_history(context: &VulkanContext) -> &[ScopeHistory] {
+    match context.internal.as_ref().and_then(|internal| internal.profiler.as_ref()) {
+        Some(profiler) => profiler.history(),
+        None => &[],
+    }
+}
+
+/// Time the CPU spent blocked on the GPU in the last `begin_frame`, pass it to `tick_frame`
+pub fn fence_wait_time(context: &VulkanContext) -> Duration {
+    context.internal.as_ref().map_or(Duration::ZERO, |internal| internal.fence_wait)
+}
+
+/// Records a compute dispatch, only valid in a frame outside of the draw pass
+pub fn dispatch(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+    bindings: &[Binding],
+    push_constants: &[u8],
+    group_count: [u32; 3],
+) -> Result<(), VulkanError> {
+    let (internal, _) = bind_compute(context, pipeline, bindings, push_constants)?;
+    let command_buffer = internal.frames[internal.current_frame].command_buffer;
+    unsafe {
+        internal.device.cmd_dispatch(
+            command_buffer,
+            group_count[0],
+            group_count[1],
+            group_count[2],
+        );
+    }
+    Ok(())
+}
+
+/// Same as `dispatch`, with group counts read from `buffer` as a `VkDispatchIndirectCommand`
+pub fn dispatch_indirect(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+    bindings: &[Binding],
+    push_constants: &[u8],
+    buffer: BufferHandle,
+    offset: u64,
+) -> Result<(), VulkanError> {
+    let (internal, resources) = bind_compute(context, pipeline, bindings, push_constants)?;
+    let buffer = resources.get_buffer(buffer)?;
+    let command_buffer = internal.frames[internal.current_frame].command_buffer;
+    unsafe {
+        internal.device.cmd_dispatch_indirect(command_buffer, buffer.raw, offset);
+    }
+    Ok(())
+}
+
+pub fn memory_barrier(context: &mut VulkanContext, barrier: Barrier) -> Result<(), VulkanError> {
+    let Some(internal) = context.internal.as_ref() else {
+        return Err(VulkanError::NoFrameInProgress);
+    };
+    if internal.present_index.is_none() {
+        return Err(VulkanError::NoFrameInProgress);
+    }
+    if context.pass.as_ref().is_some_and(|pass| pass.active) {
+        return Err(VulkanError::InsideRenderPass);
+    }
+
+    let (src_stage, src_access, dst_stage, dst_access) = match barrier {
+        Barrier::ComputeToCompute => (
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        ),
+        Barrier::ComputeToVertexInput => (
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::INDIRECT_COMMAND_READ
+                | vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+                | vk::AccessFlags::INDEX_READ,
+        ),
+        Barrier::ComputeToGraphics => (
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        ),
+        Barrier::GraphicsToCompute => (
+            vk::PipelineStageFlags::VERTEX_SHADER
+                | vk::PipelineStageFlags::FRAGMENT_SHADER
+                | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        ),
+        Barrier::ComputeToHost => (
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::HOST_READ,
+        ),
+    };
+    let memory_barrier =
+        vk::MemoryBarrier::default().src_access_mask(src_access).dst_access_mask(dst_access);
+    unsafe {
+        internal.device.cmd_pipeline_barrier(
+            internal.frames[internal.current_frame].command_buffer,
+            src_stage,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[memory_barrier],
+            &[],
+            &[],
+        );
+    }
+    Ok(())
+}
+
 pub fn end_frame(context: &mut VulkanContext) {
+    end_frame_submit(context);
+    if context.panic_on_validation_error {
+        if let Some(error) = context.validation_log.first_error() {
+            panic!("Validation error: {}", error);
+        }
+    }
+}
+
+/// Part of `VulkanContext::debug` the driver supports, `None` before the context is created
+pub fn enabled_debug(context: &VulkanContext) -> Option<DebugConfig> {
+    context.internal.as_ref().map(|internal| internal.debug)
+}
+
+/// Messages reported by the validation layer so far, oldest first
+pub fn validation_messages(context: &VulkanContext) -> Vec<ValidationMessage> {
+    context.validation_log.messages()
+}
+
+/// Same as `validation_messages`, but also clears the log
+pub fn take_validation_messages(context: &mut VulkanContext) -> Vec<ValidationMessage> {
+    context.validation_log.take()
+}
+
+/// Copies the current swapchain image into CPU memory once the frame is submitted.
+/// Call after the passes of the frame are drawn and before `end_frame`,
+/// then pick the result up with `take_capture`. Fails with `TargetNotDrawn` if no draw
+/// pass ran this frame, the image has no defined contents then
+pub fn capture_frame(context: &mut VulkanContext) -> Result<(), VulkanError> {
+    if context.pass.as_ref().is_some_and(|pass| pass.active) {
+        return Err(VulkanError::InsideRenderPass);
+    }
+    let Some(internal) = context.internal.as_mut() else {
+        return Err(VulkanError::NoFrameInProgress);
+    };
+    let Some(present_index) = internal.present_index else {
+        return Err(VulkanError::NoFrameInProgress);
+    };
+    if !internal.target_drawn {
+        return Err(VulkanError::TargetNotDrawn);
+    }
+    if !internal.swapchain_usage.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+        return Err(VulkanError::CaptureNotSupported);
+    }
+    let image = internal.swapchain_images[present_index as usize];
+    let layout = internal.target_layout;
+    let extent = internal.surface_resolution;
+    let format = internal.surface_format.format;
+    record_readback(internal, image, layout, extent, format)
+}
+
+pub fn take_capture(context: &mut VulkanContext) -> Option<CapturedImage> {
+    context.internal.as_mut().and_then(|internal| internal.captured.take())
+}
+
+pub fn create_pipeline(
+    context: &mut VulkanContext,
+    config: PipelineConfig,
+) -> Result<PipelineHandle, VulkanError> {
+    if let (Some(internal), Some(resources), Some(pass)) =
+        (context.internal.as_mut(), context.resources.as_mut(), context.pass.as_ref())
+    {
+        resources.create_pipeline(internal, pass, config)
+    } else {
+        Err(VulkanError::ResourceCreationFailed)
+    }
+}
+
+pub fn create_compute_pipeline(
+    context: &mut VulkanContext,
+    config: ComputePipelineConfig,
+) -> Result<PipelineHandle, VulkanError> {
+    if let (Some(internal), Some(resources)) =
+        (context.internal.as_ref(), context.resources.as_mut())
+    {
+        resources.create_compute_pipeline(internal, config)
+    } else {
+        Err(VulkanError::ResourceCreationFailed)
+    }
+}
+
+/// Replaces the shaders of a graphics pipeline, keeping its handle and the rest of its config.
+/// The old pipeline is destroyed once the frames in flight are done with it.
+/// On error the old pipeline stays in use
+pub fn recreate_pipeline(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+    vertex_source: &[u8],
+    fragment_source: &[u8],
+) -> Result<(), VulkanError> {
+    let (Some(internal), Some(resources), Some(pass)) =
+        (context.internal.as_ref(), context.resources.as_mut(), context.pass.as_ref())
+    else {
+        return Err(VulkanError::ResourceCreationFailed);
+    };
+    resources.recreate_pipeline(internal, pass, pipeline, vertex_source, fragment_source)
+}
+
+/// Compute counterpart of `recreate_pipeline`
+pub fn recreate_compute_pipeline(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+    shader_source: &[u8],
+) -> Result<(), VulkanError> {
+    let (Some(internal), Some(resources)) = (context.internal.as_ref(), context.resources.as_mut())
+    else {
+        return Err(VulkanError::ResourceCreationFailed);
+    };
+    resources.recreate_compute_pipeline(internal, pipeline, shader_source)
+}
+
+/// Starts watching GLSL sources in `directory` for pipelines registered with `watch_pipeline`
+#[cfg(feature = "hot-reload")]
+pub fn watch_shaders(context: &mut VulkanContext, directory: impl Into<PathBuf>) {
+    if context.shader_reloader.is_none() {
+        context.shader_reloader = ShaderReloader::new(directory.into());
+    }
+}
+
+/// Rebuilds `pipeline` when one of its sources, relative to the watched directory, changes
+#[cfg(feature = "hot-reload")]
+pub fn watch_pipeline(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+    vertex: &str,
+    fragment: &str,
+) {
+    if let Some(reloader) = context.shader_reloader.as_mut() {
+        reloader.watch_graphics(pipeline, vertex, fragment);
+    }
+}
+
+#[cfg(feature = "hot-reload")]
+pub fn watch_compute_pipeline(context: &mut VulkanContext, pipeline: PipelineHandle, shader: &str) {
+    if let Some(reloader) = context.shader_reloader.as_mut() {
+        reloader.watch_compute(pipeline, shader);
+    }
+}
+
+/// Recompiles changed shaders and rebuilds their pipelines without waiting for the GPU.
+/// Pipelines that fail to compile or link keep running with the old shaders
+#[cfg(feature = "hot-reload")]
+pub fn reload_shaders(context: &mut VulkanContext) {
+    let Some(reloader) = context.shader_reloader.as_mut() else {
+        return;
+    };
+    for reload in reloader.poll() {
+        let (pipeline, result) = match reload {
+            Reload::Graphics { pipeline, vertex, fragment } => {
+                (pipeline, recreate_pipeline(context, pipeline, &vertex, &fragment))
+            }
+            Reload::Compute { pipeline, shader } => {
+                (pipeline, recreate_compute_pipeline(context, pipeline, &shader))
+            }
+        };
+        match result {
+            Ok(()) => utils::trace(format!("{} reloaded", pipeline)),
+            Err(e) => utils::error(format!("{} reload failed: {:?}", pipeline, e)),
+        }
+    }
+}
+
+pub fn create_buffer(
+    context: &mut VulkanContext,
+    config: BufferConfig,
+) -> Result<BufferHandle, VulkanError> {
+    if let (Some(internal), Some(resources)) =
+        (context.internal.as_ref(), context.resources.as_mut())
+    {
+        resources.create_buffer(internal, config)
+    } else {
+        Err(VulkanError::ResourceCreationFailed)
+    }
+}
+
+pub fn create_texture(
+    context: &mut VulkanContext,
+    config: TextureConfig,
+) -> Result<TextureHandle, VulkanError> {
+    if let (Some(internal), Some(resources)) =
+        (context.internal.as_ref(), context.resources.as_mut())
+    {
+        resources.create_texture(internal, config)
+    } else {
+        Err(VulkanError::ResourceCreationFailed)
+    }
+}
+
+/// Copies `data` into a host visible buffer. The GPU must not be using that range.
+/// Empty ranges and ranges past the end fail with `BufferOutOfRange`
+pub fn write_buffer(
+    context: &mut VulkanContext,
+    buffer: BufferHandle,
+    offset: u64,
+    data: &[u8],
+) -> Result<(), VulkanError> {
+    if let (Some(internal), Some(resources)) =
+        (context.internal.as_ref(), context.resources.as_ref())
+    {
+        resources.write_buffer(internal, buffer, offset, data)
+    } else {
+        Err(VulkanError::BufferNotFound)
+    }
+}
+
+/// Copies a host visible buffer into `data`. Results of GPU writes are visible
+/// after a `Barrier::ComputeToHost` and once the frame that wrote them has completed
+pub fn read_buffer(
+    context: &mut VulkanContext,
+    buffer: BufferHandle,
+    offset: u64,
+    data: &mut [u8],
+) -> Result<(), VulkanError> {
+    if let (Some(internal), Some(resources)) =
+        (context.internal.as_ref(), context.resources.as_ref())
+    {
+        resources.read_buffer(internal, buffer, offset, data)
+    } else {
+        Err(VulkanError::BufferNotFound)
+    }
+}
+
+/// Invalidates the handle right away, the pipeline itself is destroyed once the frames
+/// in flight are done with it. Can be called at any point, also while recording a frame
+pub fn destroy_pipeline(
+    context: &mut VulkanContext,
+    pipeline: PipelineHandle,
+) -> Result<(), VulkanError> {
+    match (context.internal.as_ref(), context.resources.as_mut()) {
+        (Some(internal), Some(resources)) => resources.destroy_pipeline(internal, pipeline),
+        _ => Err(VulkanError::PipelineNotFound),
+    }
+}
+
+/// Same as `destroy_pipeline` for buffers
+pub fn destroy_buffer(
+    context: &mut VulkanContext,
+    buffer: BufferHandle,
+) -> Result<(), VulkanError> {
+    match (context.internal.as_ref(), context.resources.as_mut()) {
+        (Some(internal), Some(resources)) => resources.destroy_buffer(internal, buffer),
+        _ => Err(VulkanError::BufferNotFound),
+    }
+}
+
+/// Same as `destroy_pipeline` for textures
+pub fn destroy_texture(
+    context: &mut VulkanContext,
+    texture: TextureHandle,
+) -> Result<(), VulkanError> {
+    match (context.internal.as_ref(), context.resources.as_mut()) {
+        (Some(internal), Some(resources)) => resources.destroy_texture(internal, texture),
+        _ => Err(VulkanError::TextureNotFound),
+    }
+}
+
+pub fn wait_idle(context: &mut VulkanContext) {
+    if let Some(internal) = context.internal.as_ref() {
+        unsafe { internal.device.device_wait_idle().expect("Wait idle error") };
+    }
+}
+
+// PRIVATE FUNCTIONS ===========================================================================
+fn end_frame_submit(context: &mut VulkanContext) {
     if let Some(internal) = context.internal.as_mut() {
         if internal.present_index.is_none() {
             return;
@@ -132,6 +824,10 @@ pub fn end_frame(context: &mut VulkanContext) {
         unsafe {
             let frame = &internal.frames[internal.current_frame];
             let present_index = internal.present_index.take().unwrap();
+            let reuse_fence = frame.reuse_fence;
+            if let Some(profiler) = internal.profiler.as_mut() {
+                profiler.end_frame(&internal.device, internal.current_frame, frame.command_buffer);
+            }
             internal.current_frame = (internal.current_frame + 1) % FRAMES_IN_FLIGHT;
 
             internal
@@ -140,6 +836,16 @@ pub fn end_frame(context: &mut VulkanContext) {
                 .expect("End command buffer failed");
 
             let command_buffers = [frame.command_buffer];
+            if internal.offscreen.is_some() {
+                let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+                internal
+                    .device
+                    .queue_submit(internal.present_queue, &[submit_info], reuse_fence)
+                    .expect("Submit failed");
+                resolve_capture(internal, reuse_fence);
+                return;
+            }
+
             let wait_semaphores = [frame.presentation_complete_semaphore];
             let signal_semaphores = [frame.rendering_complete_semaphore];
             let wait_dst_stage_mask = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
@@ -166,36 +872,98 @@ pub fn end_frame(context: &mut VulkanContext) {
                 Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => internal.out_of_date = true,
                 Err(e) => panic!("{e}"),
             }
+            resolve_capture(internal, reuse_fence);
         };
     }
 }
 
-pub fn create_pipeline(
-    context: &mut VulkanContext,
-    config: PipelineConfig,
-) -> Result<u32, VulkanError> {
-    if let (Some(internal), Some(resources)) =
-        (context.internal.as_mut(), context.resources.as_mut())
-    {
-        resources.create_pipeline(internal, config)
-    } else {
-        Err(VulkanError::ResourceCreationFailed)
+fn bind_compute<'a>(
+    context: &'a mut VulkanContext,
+    pipeline: PipelineHandle,
+    bindings: &[Binding],
+    push_constants: &[u8],
+) -> Result<(&'a InternalContext, &'a Resources), VulkanError> {
+    let (Some(internal), Some(resources)) = (context.internal.as_mut(), context.resources.as_ref())
+    else {
+        return Err(VulkanError::NoFrameInProgress);
+    };
+    if internal.present_index.is_none() {
+        return Err(VulkanError::NoFrameInProgress);
+    }
+    if context.pass.as_ref().is_some_and(|pass| pass.active) {
+        return Err(VulkanError::InsideRenderPass);
     }
+    let pipeline = resources.get_pipeline(pipeline)?;
+    if pipeline.bind_point != vk::PipelineBindPoint::COMPUTE {
+        return Err(VulkanError::WrongPipelineKind);
+    }
+    resources.bind_pipeline(internal, pipeline, bindings, push_constants)?;
+    Ok((internal, resources))
 }
 
-impl Drop for VulkanContext {
-    fn drop(&mut self) {
+/// Destroys the deferred resources and frees the descriptor sets of every frame the GPU
+/// has finished, for while no frames are recorded. Their fences stay signaled
+fn release_completed_frames(internal: &mut InternalContext, resources: &mut Resources) {
+    for frame in 0..FRAMES_IN_FLIGHT {
+        let fence = internal.frames[frame].reuse_fence;
+        if unsafe { internal.device.get_fence_status(fence) } == Ok(true) {
+            resources.flush_deletion_queue(internal, frame);
+            reset_descriptor_pools(internal, frame);
+        }
+    }
+}
+
+fn begin_scope(internal: &mut InternalContext, name: &str) {
+    let command_buffer = internal.frames[internal.current_frame].command_buffer;
+    if let Some(profiler) = internal.profiler.as_mut() {
+        profiler.begin_scope(&internal.device, internal.current_frame, command_buffer, name);
+    }
+}
+
+fn end_scope(internal: &mut InternalContext) {
+    let command_buffer = internal.frames[internal.current_frame].command_buffer;
+    if let Some(profiler) = internal.profiler.as_mut() {
+        profiler.end_scope(&internal.device, internal.current_frame, command_buffer);
+    }
+}
+
+fn recording_frame(context: &VulkanContext) -> Option<(&InternalContext, vk::CommandBuffer)> {
+    let internal = context.internal.as_ref()?;
+    internal.present_index?;
+    Some((internal, internal.frames[internal.current_frame].command_buffer))
+}
+
+fn resolve_capture(internal: &mut InternalContext, fence: vk::Fence) {
+    if internal.readback.as_ref().is_some_and(|r| r.pending) {
         unsafe {
-            if let Some(mut internal) = self.internal.take() {
-                internal.device.device_wait_idle().expect("Wait idle error");
-                if let Some(mut resources) = self.resources.take() {
-                    destroy_resources(&mut resources, &internal);
-                }
-                if let Some(mut pass) = self.pass.take() {
-                    destroy_pass(&mut pass, &internal);
-                }
-                destroy_context(&mut internal);
+            internal.device.wait_for_fences(&[fence], true, u64::MAX).expect("Wait failed");
+        }
+        internal.captured = resolve_readback(internal);
+    }
+}
+
+/// Waits for the GPU, saves the pipeline cache and destroys the resources, the pass and
+/// the device. The context is created again by `update_context` if the window allows it
+fn destroy_device_objects(context: &mut VulkanContext) {
+    unsafe {
+        if let Some(mut internal) = context.internal.take() {
+            internal.device.device_wait_idle().expect("Wait idle error");
+            if let Some(path) = context.pipeline_cache_path.as_deref() {
+                save_pipeline_cache(&internal, path);
             }
+            if let Some(mut resources) = context.resources.take() {
+                destroy_resources(&mut resources, &internal);
+            }
+            if let Some(mut pass) = context.pass.take() {
+                destroy_pass(&mut pass, &internal);
+            }
+            destroy_context(&mut internal);
         }
     }
 }
+
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        destroy_device_objects(self);
+    }
+}
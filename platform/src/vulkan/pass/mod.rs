@@ -1,6 +1,9 @@
 use ash::vk;
 
-use super::InternalContext;
+use super::{allocate_memory, begin_label, end_label, set_object_name, InternalContext};
+
+const DEPTH_FORMATS: [vk::Format; 3] =
+    [vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT];
 
 pub struct PassConfiguration {
     pub clear_color: [f32; 4],
@@ -8,33 +11,67 @@ pub struct PassConfiguration {
 
 pub struct Pass {
     pub raw: vk::RenderPass,
-    pub clear_value: vk::ClearValue,
+    pub clear_values: [vk::ClearValue; 2],
     pub framebuffers: Vec<vk::Framebuffer>,
+    pub color_attachment_count: u32,
+    pub depth_format: vk::Format,
+    /// Shared by all framebuffers, frames are rendered one after another on the same queue
+    pub depth: DepthTarget,
+    pub active: bool,
+}
+
+pub struct DepthTarget {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
 }
 
 pub fn create_pass(internal: &mut InternalContext, pass_config: &PassConfiguration) -> Pass {
-    let attachments = [vk::AttachmentDescription {
-        format: internal.surface_format.format,
-        samples: vk::SampleCountFlags::TYPE_1,
-        load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::STORE,
-        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-        ..Default::default()
-    }];
+    let depth_format = pick_depth_format(internal);
+    let attachments = [
+        vk::AttachmentDescription {
+            format: internal.surface_format.format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            final_layout: internal.target_layout,
+            ..Default::default()
+        },
+        vk::AttachmentDescription {
+            format: depth_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            stencil_load_op: vk::AttachmentLoadOp::CLEAR,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        },
+    ];
     let color_attachment_refs = [vk::AttachmentReference {
         attachment: 0,
         layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
     }];
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 1,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+    let depth_stages =
+        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
     let subpass_deps = [vk::SubpassDependency {
         src_subpass: vk::SUBPASS_EXTERNAL,
-        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages,
+        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages,
+        src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
         dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
-            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
         ..Default::default()
     }];
     let subpasses = [vk::SubpassDescription::default()
         .color_attachments(&color_attachment_refs)
+        .depth_stencil_attachment(&depth_attachment_ref)
         .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)];
 
     let create_info = vk::RenderPassCreateInfo::default()
@@ -45,43 +82,60 @@ pub fn create_pass(internal: &mut InternalContext, pass_config: &PassConfigurati
         internal.device.create_render_pass(&create_info, None).expect("Cant create render pass")
     };
 
-    let framebuffers = create_framebuffers(
-        &internal.device,
-        &internal.swapchain_image_views,
-        raw,
-        internal.surface_resolution.width,
-        internal.surface_resolution.height,
-    );
+    set_object_name(internal, raw, "Draw pass");
+
+    let depth = create_depth_target(internal, depth_format);
+    let framebuffers = create_framebuffers(internal, depth.view, raw);
 
     Pass {
         raw,
-        clear_value: vk::ClearValue {
-            color: vk::ClearColorValue { float32: pass_config.clear_color },
-        },
+        clear_values: [
+            vk::ClearValue { color: vk::ClearColorValue { float32: pass_config.clear_color } },
+            vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
+        ],
         framebuffers,
+        color_attachment_count: color_attachment_refs.len() as u32,
+        depth_format,
+        depth,
+        active: false,
     }
 }
 
-pub fn begin_pass(context: &InternalContext, pass: &Pass, present_index: u32) {
+pub fn begin_pass(context: &InternalContext, pass: &mut Pass, present_index: u32) {
+    let command_buffer = context.frames[context.current_frame].command_buffer;
+    let extent = context.surface_resolution;
+    let viewports = [vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: extent.width as f32,
+        height: extent.height as f32,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    }];
+    let scissors = [extent.into()];
+    pass.active = true;
+    begin_label(context, command_buffer, "Draw pass", [0.2, 0.4, 0.8, 1.0]);
     unsafe {
-        let clear_values = [pass.clear_value];
         let begin_info = vk::RenderPassBeginInfo::default()
             .render_pass(pass.raw)
-            .clear_values(&clear_values)
+            .clear_values(&pass.clear_values)
             .render_area(context.surface_resolution.into())
             .framebuffer(pass.framebuffers[present_index as usize]);
         context.device.cmd_begin_render_pass(
-            context.frames[context.current_frame].command_buffer,
+            command_buffer,
             &begin_info,
             vk::SubpassContents::INLINE,
         );
+        context.device.cmd_set_viewport(command_buffer, 0, &viewports);
+        context.device.cmd_set_scissor(command_buffer, 0, &scissors);
     }
 }
 
-pub fn end_pass(context: &InternalContext) {
-    unsafe {
-        context.device.cmd_end_render_pass(context.frames[context.current_frame].command_buffer);
-    }
+pub fn end_pass(context: &InternalContext, pass: &mut Pass) {
+    let command_buffer = context.frames[context.current_frame].command_buffer;
+    pass.active = false;
+    unsafe { context.device.cmd_end_render_pass(command_buffer) };
+    end_label(context, command_buffer);
 }
 
 pub fn destroy_pass(pass: &mut Pass, context: &InternalContext) {
@@ -91,29 +145,113 @@ pub fn destroy_pass(pass: &mut Pass, context: &InternalContext) {
         }
         context.device.destroy_render_pass(pass.raw, None);
     }
+    destroy_depth_target(&pass.depth, &context.device);
 }
 
+/// Depth buffer matching the current surface resolution
+pub fn create_depth_target(internal: &InternalContext, format: vk::Format) -> DepthTarget {
+    let extent = internal.surface_resolution;
+    let create_info = vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .extent(vk::Extent3D { width: extent.width, height: extent.height, depth: 1 })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED);
+    let mut aspect_mask = vk::ImageAspectFlags::DEPTH;
+    if format != vk::Format::D32_SFLOAT {
+        aspect_mask |= vk::ImageAspectFlags::STENCIL;
+    }
+    unsafe {
+        let device = &internal.device;
+        let image = device.create_image(&create_info, None).expect("Cant create depth image");
+        let requirements = device.get_image_memory_requirements(image);
+        let memory = allocate_memory(
+            device,
+            &internal.memory_properties,
+            requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .expect("Cant allocate depth image memory");
+        device.bind_image_memory(image, memory, 0).expect("Bind image memory failed");
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image(image);
+        let view = device.create_image_view(&view_info, None).expect("Cant create image view");
+        set_object_name(internal, image, "Depth target");
+        set_object_name(internal, memory, "Depth target memory");
+        set_object_name(internal, view, "Depth target view");
+        DepthTarget { image, memory, view }
+    }
+}
+
+pub fn destroy_depth_target(depth: &DepthTarget, device: &ash::Device) {
+    unsafe {
+        device.destroy_image_view(depth.view, None);
+        device.destroy_image(depth.image, None);
+        device.free_memory(depth.memory, None);
+    }
+}
+
+/// One framebuffer per swapchain image, all sharing the depth target
 pub fn create_framebuffers(
-    device: &ash::Device,
-    swapchain_image_views: &[vk::ImageView],
+    context: &InternalContext,
+    depth_view: vk::ImageView,
     pass: vk::RenderPass,
-    width: u32,
-    height: u32,
 ) -> Vec<vk::Framebuffer> {
-    swapchain_image_views
+    let extent = context.surface_resolution;
+    context
+        .swapchain_image_views
         .iter()
-        .map(|&image_view| {
-            let attachments = [image_view];
+        .enumerate()
+        .map(|(index, &image_view)| {
+            let attachments = [image_view, depth_view];
             let create_info = vk::FramebufferCreateInfo::default()
                 .render_pass(pass)
                 .attachments(&attachments)
-                .width(width)
-                .height(height)
+                .width(extent.width)
+                .height(extent.height)
                 .layers(1);
 
-            unsafe {
-                device.create_framebuffer(&create_info, None).expect("Cant create framebuffer")
-            }
+            let framebuffer = unsafe {
+                context
+                    .device
+                    .create_framebuffer(&create_info, None)
+                    .expect("Cant create framebuffer")
+            };
+            set_object_name(context, framebuffer, &format!("Framebuffer {}", index));
+            framebuffer
         })
         .collect()
 }
+
+// PRIVATE FUNCTIONS ==========================================================================
+/// Prefers formats with a stencil component so stencil state can be used
+fn pick_depth_format(internal: &InternalContext) -> vk::Format {
+    DEPTH_FORMATS
+        .into_iter()
+        .find(|format| {
+            let properties = unsafe {
+                internal
+                    .instance
+                    .get_physical_device_format_properties(internal.physical_device, *format)
+            };
+            properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .unwrap_or(vk::Format::D32_SFLOAT)
+}
@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use ash::vk;
+
+use super::constants::{FRAMES_IN_FLIGHT, GPU_HISTORY_LENGTH, MAX_TIMESTAMP_QUERIES};
+use crate::utils;
+
+/// GPU time of one scope in a resolved frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeTiming {
+    pub name: String,
+    /// Nesting level, 0 for the whole frame
+    pub depth: u32,
+    pub milliseconds: f64,
+}
+
+/// Timings of one scope name over the last `GPU_HISTORY_LENGTH` resolved frames
+#[derive(Debug, Clone)]
+pub struct ScopeHistory {
+    pub name: String,
+    pub samples: VecDeque<f64>,
+}
+
+/// Timestamp queries of the frames in flight, see `begin_gpu_scope`
+pub struct GpuProfiler {
+    /// Nanoseconds per timestamp tick
+    period: f64,
+    /// Bits of the timestamps that are valid on the queue
+    mask: u64,
+    frames: [ProfilerFrame; FRAMES_IN_FLIGHT],
+    timings: Vec<ScopeTiming>,
+    history: Vec<ScopeHistory>,
+}
+
+pub struct ProfilerFrame {
+    pub query_pool: vk::QueryPool,
+    scopes: Vec<Scope>,
+    /// Indices into `scopes` of the scopes that are still open, `None` for skipped ones
+    open: Vec<Option<usize>>,
+    next_query: u32,
+}
+
+struct Scope {
+    name: String,
+    depth: u32,
+    begin: u32,
+    end: Option<u32>,
+}
+
+impl ScopeHistory {
+    pub fn last(&self) -> f64 {
+        self.samples.back().copied().unwrap_or(0.0)
+    }
+
+    pub fn average(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+
+    pub fn max(&self) -> f64 {
+        self.samples.iter().copied().fold(0.0, f64::max)
+    }
+}
+
+impl fmt::Display for ScopeHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: last {:.3}ms, avg {:.3}ms, max {:.3}ms",
+            self.name,
+            self.last(),
+            self.average(),
+            self.max()
+        )
+    }
+}
+
+impl GpuProfiler {
+    pub fn frame(&self, frame: usize) -> &ProfilerFrame {
+        &self.frames[frame]
+    }
+
+    /// Scopes of the latest resolved frame, in the order they were opened
+    pub fn timings(&self) -> &[ScopeTiming] {
+        &self.timings
+    }
+
+    /// One entry per scope name, in the order the names were first seen
+    pub fn history(&self) -> &[ScopeHistory] {
+        &self.history
+    }
+
+    /// Reads the results of the frame previously recorded in `frame`, whose fence has
+    /// signaled, then resets its queries and opens the `Frame` scope
+    pub fn begin_frame(
+        &mut self,
+        device: &ash::Device,
+        frame: usize,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        self.resolve(device, frame);
+        let profiler_frame = &mut self.frames[frame];
+        profiler_frame.scopes.clear();
+        profiler_frame.open.clear();
+        profiler_frame.next_query = 0;
+        unsafe {
+            device.cmd_reset_query_pool(
+                command_buffer,
+                profiler_frame.query_pool,
+                0,
+                MAX_TIMESTAMP_QUERIES,
+            )
+        };
+        self.begin_scope(device, frame, command_buffer, "Frame");
+    }
+
+    /// Closes the scopes left open, including the `Frame` scope
+    pub fn end_frame(
+        &mut self,
+        device: &ash::Device,
+        frame: usize,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let open = self.frames[frame].open.len();
+        if open > 1 {
+            utils::error(format!("{} GPU scopes were not closed", open - 1));
+        }
+        for _ in 0..open {
+            self.end_scope(device, frame, command_buffer);
+        }
+    }
+
+    pub fn begin_scope(
+        &mut self,
+        device: &ash::Device,
+        frame: usize,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+    ) {
+        let frame = &mut self.frames[frame];
+        // Open scopes still need their end query, scopes past the limit are skipped
+        if frame.next_query + frame.open.len() as u32 + 2 > MAX_TIMESTAMP_QUERIES {
+            frame.open.push(None);
+            return;
+        }
+        let begin = frame.next_query;
+        frame.next_query += 1;
+        unsafe {
+            device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                frame.query_pool,
+                begin,
+            )
+        };
+        frame.open.push(Some(frame.scopes.len()));
+        frame.scopes.push(Scope {
+            name: name.to_string(),
+            depth: frame.open.len() as u32 - 1,
+            begin,
+            end: None,
+        });
+    }
+
+    pub fn end_scope(
+        &mut self,
+        device: &ash::Device,
+        frame: usize,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let frame = &mut self.frames[frame];
+        let Some(Some(scope)) = frame.open.pop() else {
+            return;
+        };
+        let end = frame.next_query;
+        frame.next_query += 1;
+        unsafe {
+            device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                frame.query_pool,
+                end,
+            )
+        };
+        frame.scopes[scope].end = Some(end);
+    }
+
+    fn resolve(&mut self, device: &ash::Device, frame: usize) {
+        let frame = &mut self.frames[frame];
+        if frame.next_query == 0 {
+            return;
+        }
+        let mut data = vec![0u64; frame.next_query as usize];
+        let result = unsafe {
+            device.get_query_pool_results(
+                frame.query_pool,
+                0,
+                &mut data,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+        if let Err(e) = result {
+            utils::error(format!("Cant read GPU timestamps: {}", e));
+            return;
+        }
+
+        self.timings.clear();
+        for scope in frame.scopes.iter() {
+            let Some(end) = scope.end else {
+                continue;
+            };
+            let ticks = data[end as usize].wrapping_sub(data[scope.begin as usize]) & self.mask;
+            let milliseconds = ticks as f64 * self.period / 1_000_000.0;
+            self.timings.push(ScopeTiming {
+                name: scope.name.clone(),
+                depth: scope.depth,
+                milliseconds,
+            });
+
+            let history = match self.history.iter_mut().position(|h| h.name == scope.name) {
+                Some(index) => &mut self.history[index],
+                None => {
+                    self.history
+                        .push(ScopeHistory { name: scope.name.clone(), samples: VecDeque::new() });
+                    self.history.last_mut().unwrap()
+                }
+            };
+            if history.samples.len() == GPU_HISTORY_LENGTH {
+                history.samples.pop_front();
+            }
+            history.samples.push_back(milliseconds);
+        }
+    }
+}
+
+/// `None` if the queue can't write timestamps
+pub fn create_gpu_profiler(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    queue_family_index: u32,
+    device: &ash::Device,
+) -> Option<GpuProfiler> {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let queue_families =
+        unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+    let valid_bits = queue_families[queue_family_index as usize].timestamp_valid_bits;
+    if valid_bits == 0 {
+        utils::trace("Timestamps are not supported, GPU profiling is disabled");
+        return None;
+    }
+
+    let create_info = vk::QueryPoolCreateInfo::default()
+        .query_type(vk::QueryType::TIMESTAMP)
+        .query_count(MAX_TIMESTAMP_QUERIES);
+    let frames = std::array::from_fn(|_| ProfilerFrame {
+        query_pool: unsafe {
+            device.create_query_pool(&create_info, None).expect("Cant create query pool")
+        },
+        scopes: vec![],
+        open: vec![],
+        next_query: 0,
+    });
+    Some(GpuProfiler {
+        period: properties.limits.timestamp_period as f64,
+        mask: if valid_bits >= 64 { u64::MAX } else { (1 << valid_bits) - 1 },
+        frames,
+        timings: vec![],
+        history: vec![],
+    })
+}
+
+pub fn destroy_gpu_profiler(profiler: &GpuProfiler, device: &ash::Device) {
+    for frame in profiler.frames.iter() {
+        unsafe { device.destroy_query_pool(frame.query_pool, None) };
+    }
+}
@@ -0,0 +1,174 @@
+use ash::vk;
+
+use super::{
+    allocate_memory, set_object_name, BufferHandle, Garbage, InternalContext, Resources,
+    VulkanError,
+};
+use crate::utils;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BufferUsage {
+    pub storage: bool,
+    pub uniform: bool,
+    pub vertex: bool,
+    pub index: bool,
+    pub indirect: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BufferConfig {
+    pub size: u64,
+    pub usage: BufferUsage,
+    /// Host visible buffers can be filled and read back with `write_buffer` and `read_buffer`
+    pub host_visible: bool,
+}
+
+pub struct Buffer {
+    pub raw: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub size: u64,
+    pub host_visible: bool,
+}
+
+impl BufferUsage {
+    fn to_vk(self) -> vk::BufferUsageFlags {
+        let mut flags = vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST;
+        if self.storage {
+            flags |= vk::BufferUsageFlags::STORAGE_BUFFER;
+        }
+        if self.uniform {
+            flags |= vk::BufferUsageFlags::UNIFORM_BUFFER;
+        }
+        if self.vertex {
+            flags |= vk::BufferUsageFlags::VERTEX_BUFFER;
+        }
+        if self.index {
+            flags |= vk::BufferUsageFlags::INDEX_BUFFER;
+        }
+        if self.indirect {
+            flags |= vk::BufferUsageFlags::INDIRECT_BUFFER;
+        }
+        flags
+    }
+}
+
+impl Resources {
+    pub fn create_buffer(
+        &mut self,
+        context: &InternalContext,
+        config: BufferConfig,
+    ) -> Result<BufferHandle, VulkanError> {
+        let create_info = vk::BufferCreateInfo::default()
+            .size(config.size)
+            .usage(config.usage.to_vk())
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let memory_flags = if config.host_visible {
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+        } else {
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+        };
+
+        let buffer = unsafe {
+            let raw = context
+                .device
+                .create_buffer(&create_info, None)
+                .map_err(|_| VulkanError::ResourceCreationFailed)?;
+            let requirements = context.device.get_buffer_memory_requirements(raw);
+            let memory = allocate_memory(
+                &context.device,
+                &context.memory_properties,
+                requirements,
+                memory_flags,
+            )
+            .inspect_err(|_| context.device.destroy_buffer(raw, None))?;
+            context.device.bind_buffer_memory(raw, memory, 0).expect("Bind buffer memory failed");
+            Buffer { raw, memory, size: config.size, host_visible: config.host_visible }
+        };
+
+        let (raw, memory) = (buffer.raw, buffer.memory);
+        let handle = self.buffers.insert(buffer);
+        set_object_name(context, raw, &handle.to_string());
+        set_object_name(context, memory, &format!("{} memory", handle));
+        Ok(handle)
+    }
+
+    pub fn get_buffer(&self, handle: BufferHandle) -> Result<&Buffer, VulkanError> {
+        self.buffers.get(handle).ok_or(VulkanError::BufferNotFound)
+    }
+
+    /// Same as `destroy_pipeline` for buffers
+    pub fn destroy_buffer(
+        &mut self,
+        context: &InternalContext,
+        handle: BufferHandle,
+    ) -> Result<(), VulkanError> {
+        let buffer = self.buffers.remove(handle).ok_or(VulkanError::BufferNotFound)?;
+        self.defer_destroy(context, Garbage::Buffer(buffer));
+        Ok(())
+    }
+
+    pub fn write_buffer(
+        &self,
+        context: &InternalContext,
+        handle: BufferHandle,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), VulkanError> {
+        let buffer = self.get_buffer(handle)?;
+        let mapped = map_range(context, buffer, offset, data.len())?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), mapped, data.len());
+            context.device.unmap_memory(buffer.memory);
+        }
+        Ok(())
+    }
+
+    pub fn read_buffer(
+        &self,
+        context: &InternalContext,
+        handle: BufferHandle,
+        offset: u64,
+        data: &mut [u8],
+    ) -> Result<(), VulkanError> {
+        let buffer = self.get_buffer(handle)?;
+        let mapped = map_range(context, buffer, offset, data.len())?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(mapped as *const u8, data.as_mut_ptr(), data.len());
+            context.device.unmap_memory(buffer.memory);
+        }
+        Ok(())
+    }
+}
+
+pub fn destroy_buffer(buffer: &Buffer, device: &ash::Device) {
+    unsafe {
+        device.destroy_buffer(buffer.raw, None);
+        device.free_memory(buffer.memory, None);
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+/// Maps `len` bytes at `offset`, empty ranges can't be mapped
+fn map_range(
+    context: &InternalContext,
+    buffer: &Buffer,
+    offset: u64,
+    len: usize,
+) -> Result<*mut u8, VulkanError> {
+    if !buffer.host_visible {
+        return Err(VulkanError::BufferNotHostVisible);
+    }
+    if len == 0 || offset.checked_add(len as u64).is_none_or(|end| end > buffer.size) {
+        return Err(VulkanError::BufferOutOfRange);
+    }
+    unsafe {
+        let mapped = context
+            .device
+            .map_memory(buffer.memory, offset, len as u64, vk::MemoryMapFlags::empty())
+            .map_err(|e| {
+                utils::error(format!("Cant map buffer memory: {}", e));
+                VulkanError::ResourceCreationFailed
+            })?;
+        Ok(mapped as *mut u8)
+    }
+}
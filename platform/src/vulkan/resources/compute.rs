@@ -0,0 +1,92 @@
+use ash::vk;
+
+use super::{
+    check_stage, create_pipeline_layout, create_shader_module, destroy_set_layouts, merge_stages,
+    name_pipeline, reflect_shader, Garbage, InternalContext, Pipeline, PipelineHandle,
+    PipelineInterface, Resources, VulkanError, SHADER_ENTRY,
+};
+use crate::utils;
+
+pub struct ComputePipelineConfig<'a> {
+    pub shader_source: &'a [u8],
+}
+
+impl Resources {
+    pub fn create_compute_pipeline(
+        &mut self,
+        context: &InternalContext,
+        config: ComputePipelineConfig,
+    ) -> Result<PipelineHandle, VulkanError> {
+        utils::trace(format!("Creating compute pipeline from CS {}b", config.shader_source.len()));
+        let pipeline = build_compute_pipeline(context, config.shader_source)?;
+        let handle = self.pipelines.insert(pipeline);
+        name_pipeline(context, self.pipelines.get(handle).unwrap(), handle);
+        Ok(handle)
+    }
+
+    /// Compute counterpart of `recreate_pipeline`
+    pub fn recreate_compute_pipeline(
+        &mut self,
+        context: &InternalContext,
+        handle: PipelineHandle,
+        shader_source: &[u8],
+    ) -> Result<(), VulkanError> {
+        let old = self.get_pipeline(handle)?;
+        if old.bind_point != vk::PipelineBindPoint::COMPUTE {
+            return Err(VulkanError::WrongPipelineKind);
+        }
+        let pipeline = build_compute_pipeline(context, shader_source)?;
+        name_pipeline(context, &pipeline, handle);
+        let slot = self.pipelines.get_mut(handle).ok_or(VulkanError::PipelineNotFound)?;
+        let old = std::mem::replace(slot, pipeline);
+        self.defer_destroy(context, Garbage::Pipeline(old));
+        Ok(())
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn build_compute_pipeline(
+    context: &InternalContext,
+    shader_source: &[u8],
+) -> Result<Pipeline, VulkanError> {
+    let reflection = reflect_shader(shader_source)?;
+    check_stage(&reflection, vk::ShaderStageFlags::COMPUTE)?;
+    let interface = merge_stages(&[&reflection])?;
+
+    let device = &context.device;
+    let module = create_shader_module(device, shader_source)?;
+    let result = create_compute(device, context.pipeline_cache, module, interface);
+    unsafe { device.destroy_shader_module(module, None) };
+    result
+}
+
+fn create_compute(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    module: vk::ShaderModule,
+    interface: PipelineInterface,
+) -> Result<Pipeline, VulkanError> {
+    let (layout, set_layouts) = create_pipeline_layout(device, &interface)?;
+
+    let stage = vk::PipelineShaderStageCreateInfo::default()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(module)
+        .name(SHADER_ENTRY);
+    let create_info = vk::ComputePipelineCreateInfo::default().stage(stage).layout(layout);
+    let result = unsafe { device.create_compute_pipelines(cache, &[create_info], None) };
+    match result {
+        Ok(pipelines) => Ok(Pipeline {
+            raw: pipelines[0],
+            layout,
+            bind_point: vk::PipelineBindPoint::COMPUTE,
+            set_layouts,
+            interface,
+            graphics: None,
+        }),
+        Err(_) => {
+            unsafe { device.destroy_pipeline_layout(layout, None) };
+            destroy_set_layouts(device, &set_layouts);
+            Err(VulkanError::ResourceCreationFailed)
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use super::{
+    destroy_buffer, destroy_pipeline, destroy_texture, Buffer, InternalContext, Pipeline,
+    Resources, Texture, FRAMES_IN_FLIGHT,
+};
+
+/// Resource waiting for the frames that may still use it to complete
+pub enum Garbage {
+    Pipeline(Pipeline),
+    Buffer(Buffer),
+    Texture(Texture),
+}
+
+impl Resources {
+    /// Queues `garbage` on the latest frame that can reference it: the frame being recorded,
+    /// or the last submitted one between frames. Submissions complete in order,
+    /// so once that frame's `reuse_fence` signals no earlier frame uses it either
+    pub fn defer_destroy(&mut self, context: &InternalContext, garbage: Garbage) {
+        let frame = if context.present_index.is_some() {
+            context.current_frame
+        } else {
+            (context.current_frame + FRAMES_IN_FLIGHT - 1) % FRAMES_IN_FLIGHT
+        };
+        self.deletion_queues[frame].push(garbage);
+    }
+
+    /// Destroys everything queued on `frame`, call after waiting for its `reuse_fence`
+    pub fn flush_deletion_queue(&mut self, context: &InternalContext, frame: usize) {
+        for garbage in self.deletion_queues[frame].drain(..) {
+            destroy_garbage(garbage, &context.device);
+        }
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn destroy_garbage(garbage: Garbage, device: &ash::Device) {
+    match garbage {
+        Garbage::Pipeline(pipeline) => destroy_pipeline(&pipeline, device),
+        Garbage::Buffer(buffer) => destroy_buffer(&buffer, device),
+        Garbage::Texture(texture) => destroy_texture(&texture, device),
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Index into a `Pool` together with the generation of the slot when the handle was made.
+/// Destroying a resource bumps the generation, so old handles never alias a new resource
+pub trait ResourceHandle: Copy {
+    fn new(index: u32, generation: u32) -> Self;
+    fn index(self) -> u32;
+    fn generation(self) -> u32;
+}
+
+macro_rules! resource_handle {
+    ($($name:ident => $label:literal),* $(,)?) => {
+        $(
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct $name {
+                index: u32,
+                generation: u32,
+            }
+
+            impl ResourceHandle for $name {
+                fn new(index: u32, generation: u32) -> Self {
+                    Self { index, generation }
+                }
+
+                fn index(self) -> u32 {
+                    self.index
+                }
+
+                fn generation(self) -> u32 {
+                    self.generation
+                }
+            }
+
+            /// Short form used in logs and debug object names
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{} {}.{}", $label, self.index, self.generation)
+                }
+            }
+        )*
+    };
+}
+
+resource_handle! {
+    PipelineHandle => "Pipeline",
+    BufferHandle => "Buffer",
+    TextureHandle => "Texture",
+}
+
+/// Slot storage for resources addressed by generational handles. Freed slots are reused
+pub struct Pool<H, T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    handle: PhantomData<H>,
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+impl<H: ResourceHandle, T> Pool<H, T> {
+    pub const fn new() -> Self {
+        Self { slots: vec![], free: vec![], handle: PhantomData }
+    }
+
+    pub fn insert(&mut self, value: T) -> H {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            return H::new(index, slot.generation);
+        }
+        let index = self.slots.len() as u32;
+        self.slots.push(Slot { generation: 0, value: Some(value) });
+        H::new(index, 0)
+    }
+
+    /// `None` if the handle was never made by this pool or its resource was removed
+    pub fn get(&self, handle: H) -> Option<&T> {
+        self.slots
+            .get(handle.index() as usize)
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: H) -> Option<&mut T> {
+        self.slots
+            .get_mut(handle.index() as usize)
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    /// Takes the resource out and invalidates every handle to it
+    pub fn remove(&mut self, handle: H) -> Option<T> {
+        let slot = self
+            .slots
+            .get_mut(handle.index() as usize)
+            .filter(|slot| slot.generation == handle.generation())?;
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index());
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+}
+
+impl<H: ResourceHandle, T> Default for Pool<H, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::resources::{create_resources, Buffer};
+    use crate::vulkan::VulkanError;
+    use ash::vk;
+
+    #[test]
+    fn reuses_slot_with_new_generation() {
+        let mut pool = Pool::<BufferHandle, &str>::new();
+        let first = pool.insert("first");
+        assert_eq!(pool.remove(first), Some("first"));
+        let second = pool.insert("second");
+
+        assert_eq!(second.index(), first.index());
+        assert_eq!(second.generation(), first.generation() + 1);
+        assert_eq!(pool.get(second), Some(&"second"));
+        assert_eq!(pool.iter().count(), 1);
+    }
+
+    #[test]
+    fn stale_handle_finds_nothing() {
+        let mut pool = Pool::<BufferHandle, &str>::new();
+        let stale = pool.insert("first");
+        pool.remove(stale);
+        pool.insert("second");
+
+        assert_eq!(pool.get(stale), None);
+        assert_eq!(pool.get_mut(stale), None);
+        assert_eq!(pool.remove(stale), None);
+        assert_eq!(pool.get(BufferHandle::new(7, 0)), None);
+    }
+
+    #[test]
+    fn double_remove_fails_and_frees_once() {
+        let mut pool = Pool::<BufferHandle, &str>::new();
+        let handle = pool.insert("first");
+        assert_eq!(pool.remove(handle), Some("first"));
+        assert_eq!(pool.remove(handle), None);
+
+        // A second free would hand the same slot out twice
+        let a = pool.insert("a");
+        let b = pool.insert("b");
+        assert_ne!(a.index(), b.index());
+        assert_eq!((pool.get(a), pool.get(b)), (Some(&"a"), Some(&"b")));
+    }
+
+    #[test]
+    fn stale_handle_is_reported_as_not_found() {
+        let mut resources = create_resources();
+        let buffer = Buffer {
+            raw: vk::Buffer::null(),
+            memory: vk::DeviceMemory::null(),
+            size: 16,
+            host_visible: false,
+        };
+        let stale = resources.buffers.insert(buffer);
+        resources.buffers.remove(stale);
+
+        assert!(matches!(resources.get_buffer(stale), Err(VulkanError::BufferNotFound)));
+    }
+}
@@ -1,41 +1,543 @@
+use std::ffi::CStr;
+use std::io::Cursor;
+
 use crate::utils;
 
-use super::{InternalContext, VulkanError};
+use super::{
+    allocate_descriptor_sets, allocate_memory, constants::FRAMES_IN_FLIGHT, immediate_submit,
+    set_object_name, InternalContext, Pass, VulkanError,
+};
 use ash::vk;
 
+pub use buffer::*;
+pub use compute::*;
+pub use deletion::*;
+pub use handle::*;
+pub use reflect::*;
+pub use state::*;
+pub use texture::*;
+pub use vertex::*;
+pub mod buffer;
+pub mod compute;
+pub mod deletion;
+pub mod handle;
+pub mod reflect;
+pub mod state;
+pub mod texture;
+pub mod vertex;
+
+const SHADER_ENTRY: &CStr = c"main";
+
 pub struct Resources {
-    pub pipelines: Vec<vk::Pipeline>,
+    pub pipelines: Pool<PipelineHandle, Pipeline>,
+    pub buffers: Pool<BufferHandle, Buffer>,
+    pub textures: Pool<TextureHandle, Texture>,
+    /// Resources destroyed at runtime, per frame in flight
+    pub deletion_queues: [Vec<Garbage>; FRAMES_IN_FLIGHT],
+}
+
+pub struct Pipeline {
+    pub raw: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+    pub bind_point: vk::PipelineBindPoint,
+    /// One layout per descriptor set up to the highest set used by the shaders
+    pub set_layouts: Vec<vk::DescriptorSetLayout>,
+    /// Reflected from the shaders when the pipeline is created
+    pub interface: PipelineInterface,
+    /// Kept to rebuild the pipeline with new shaders, `None` for compute pipelines
+    pub graphics: Option<GraphicsPipelineDesc>,
+}
+
+/// Owned copy of everything in a `PipelineConfig` besides the shaders
+pub struct GraphicsPipelineDesc {
+    pub vertex_layouts: Vec<VertexLayout>,
+    pub state: PipelineState<'static>,
+    pub color_attachments: Vec<ColorAttachmentState>,
+    pub dynamic_states: Vec<DynamicState>,
 }
 
 pub struct PipelineConfig<'a> {
     pub vertext_shader_source: &'a [u8],
     pub fragment_shader_source: &'a [u8],
+    /// Vertex buffer binding `i` is described by `vertex_layouts[i]`
+    pub vertex_layouts: &'a [VertexLayout],
+    pub state: PipelineState<'a>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingType {
+    UniformBuffer,
+    StorageBuffer,
+    StorageImage,
+    SampledImage,
+    Sampler,
+    CombinedImageSampler,
+}
+
+/// Resource bound to a pipeline binding. Bindings are given ordered by set and binding,
+/// with one entry per array element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Buffer(BufferHandle),
+    Texture(TextureHandle),
+}
+
+impl BindingType {
+    /// Whether `bind_pipeline` can bind it, there are no samplers yet
+    pub fn is_supported(self) -> bool {
+        matches!(
+            self,
+            BindingType::UniformBuffer | BindingType::StorageBuffer | BindingType::StorageImage
+        )
+    }
+
+    fn to_vk(self) -> vk::DescriptorType {
+        match self {
+            BindingType::UniformBuffer => vk::DescriptorType::UNIFORM_BUFFER,
+            BindingType::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
+            BindingType::StorageImage => vk::DescriptorType::STORAGE_IMAGE,
+            BindingType::SampledImage => vk::DescriptorType::SAMPLED_IMAGE,
+            BindingType::Sampler => vk::DescriptorType::SAMPLER,
+            BindingType::CombinedImageSampler => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        }
+    }
+}
+
+impl GraphicsPipelineDesc {
+    fn new(config: &PipelineConfig) -> Self {
+        Self {
+            vertex_layouts: config.vertex_layouts.to_vec(),
+            state: PipelineState { color_attachments: &[], dynamic_states: &[], ..config.state },
+            color_attachments: config.state.color_attachments.to_vec(),
+            dynamic_states: config.state.dynamic_states.to_vec(),
+        }
+    }
+
+    fn config<'a>(&'a self, vertex: &'a [u8], fragment: &'a [u8]) -> PipelineConfig<'a> {
+        PipelineConfig {
+            vertext_shader_source: vertex,
+            fragment_shader_source: fragment,
+            vertex_layouts: &self.vertex_layouts,
+            state: PipelineState {
+                color_attachments: &self.color_attachments,
+                dynamic_states: &self.dynamic_states,
+                ..self.state
+            },
+        }
+    }
 }
 
 impl Resources {
     pub fn create_pipeline(
         &mut self,
         context: &mut InternalContext,
+        pass: &Pass,
         config: PipelineConfig,
-    ) -> Result<u32, VulkanError> {
+    ) -> Result<PipelineHandle, VulkanError> {
         utils::trace(format!(
             "Creating pipeline from VS {}b and FS {}b",
             config.vertext_shader_source.len(),
             config.fragment_shader_source.len()
         ));
-        let id = 0;
-        Ok(id)
+        let pipeline = build_graphics_pipeline(context, pass, &config)?;
+        let handle = self.pipelines.insert(pipeline);
+        name_pipeline(context, self.pipelines.get(handle).unwrap(), handle);
+        Ok(handle)
+    }
+
+    /// Rebuilds a graphics pipeline with new shaders and the config it was created with.
+    /// The old pipeline is destroyed once the frames using it complete.
+    /// On error the old pipeline is kept
+    pub fn recreate_pipeline(
+        &mut self,
+        context: &InternalContext,
+        pass: &Pass,
+        handle: PipelineHandle,
+        vertex_source: &[u8],
+        fragment_source: &[u8],
+    ) -> Result<(), VulkanError> {
+        let old = self.get_pipeline(handle)?;
+        let desc = old.graphics.as_ref().ok_or(VulkanError::WrongPipelineKind)?;
+        let config = desc.config(vertex_source, fragment_source);
+        let pipeline = build_graphics_pipeline(context, pass, &config)?;
+        name_pipeline(context, &pipeline, handle);
+        let slot = self.pipelines.get_mut(handle).ok_or(VulkanError::PipelineNotFound)?;
+        let old = std::mem::replace(slot, pipeline);
+        self.defer_destroy(context, Garbage::Pipeline(old));
+        Ok(())
+    }
+
+    pub fn get_pipeline(&self, handle: PipelineHandle) -> Result<&Pipeline, VulkanError> {
+        self.pipelines.get(handle).ok_or(VulkanError::PipelineNotFound)
+    }
+
+    /// Invalidates the handle right away and destroys the pipeline once the frames using it
+    /// complete
+    pub fn destroy_pipeline(
+        &mut self,
+        context: &InternalContext,
+        handle: PipelineHandle,
+    ) -> Result<(), VulkanError> {
+        let pipeline = self.pipelines.remove(handle).ok_or(VulkanError::PipelineNotFound)?;
+        self.defer_destroy(context, Garbage::Pipeline(pipeline));
+        Ok(())
+    }
+
+    /// Binds the pipeline together with its descriptor set and push constants
+    /// into the command buffer of the current frame
+    pub fn bind_pipeline(
+        &self,
+        context: &mut InternalContext,
+        pipeline: &Pipeline,
+        bindings: &[Binding],
+        push_constants: &[u8],
+    ) -> Result<(), VulkanError> {
+        let interface = &pipeline.interface;
+        let binding_count: u32 = interface.bindings.iter().map(|b| b.count).sum();
+        if bindings.len() != binding_count as usize
+            || push_constants.len() != interface.push_constant_size as usize
+        {
+            return Err(VulkanError::InvalidBinding);
+        }
+
+        let descriptor_sets = if pipeline.set_layouts.is_empty() {
+            vec![]
+        } else {
+            let sets = allocate_descriptor_sets(context, &pipeline.set_layouts)?;
+
+            let mut buffer_infos = Vec::with_capacity(bindings.len());
+            let mut image_infos = Vec::with_capacity(bindings.len());
+            let mut resources = bindings.iter();
+            for target in interface.bindings.iter() {
+                for _ in 0..target.count {
+                    match (resources.next(), target.kind) {
+                        (
+                            Some(Binding::Buffer(handle)),
+                            BindingType::UniformBuffer | BindingType::StorageBuffer,
+                        ) => {
+                            let buffer = self.get_buffer(*handle)?;
+                            buffer_infos.push(vk::DescriptorBufferInfo {
+                                buffer: buffer.raw,
+                                offset: 0,
+                                range: vk::WHOLE_SIZE,
+                            });
+                        }
+                        (Some(Binding::Texture(handle)), BindingType::StorageImage) => {
+                            let texture = self.get_texture(*handle)?;
+                            image_infos.push(vk::DescriptorImageInfo {
+                                sampler: vk::Sampler::null(),
+                                image_view: texture.view,
+                                image_layout: vk::ImageLayout::GENERAL,
+                            });
+                        }
+                        _ => return Err(VulkanError::InvalidBinding),
+                    }
+                }
+            }
+
+            let (mut buffer_index, mut image_index) = (0, 0);
+            let writes: Vec<_> = interface
+                .bindings
+                .iter()
+                .map(|target| {
+                    let count = target.count as usize;
+                    let write = vk::WriteDescriptorSet::default()
+                        .dst_set(sets[target.set as usize])
+                        .dst_binding(target.binding)
+                        .descriptor_type(target.kind.to_vk());
+                    if target.kind == BindingType::StorageImage {
+                        image_index += count;
+                        write.image_info(&image_infos[image_index - count..image_index])
+                    } else {
+                        buffer_index += count;
+                        write.buffer_info(&buffer_infos[buffer_index - count..buffer_index])
+                    }
+                })
+                .collect();
+            unsafe { context.device.update_descriptor_sets(&writes, &[]) };
+            sets
+        };
+
+        let frame = &context.frames[context.current_frame];
+        unsafe {
+            context.device.cmd_bind_pipeline(
+                frame.command_buffer,
+                pipeline.bind_point,
+                pipeline.raw,
+            );
+            if !descriptor_sets.is_empty() {
+                context.device.cmd_bind_descriptor_sets(
+                    frame.command_buffer,
+                    pipeline.bind_point,
+                    pipeline.layout,
+                    0,
+                    &descriptor_sets,
+                    &[],
+                );
+            }
+            if !push_constants.is_empty() {
+                context.device.cmd_push_constants(
+                    frame.command_buffer,
+                    pipeline.layout,
+                    interface.push_constant_stages,
+                    0,
+                    push_constants,
+                );
+            }
+        }
+        Ok(())
     }
 }
 
 pub const fn create_resources() -> Resources {
-    Resources { pipelines: vec![] }
+    Resources {
+        pipelines: Pool::new(),
+        buffers: Pool::new(),
+        textures: Pool::new(),
+        deletion_queues: [const { Vec::new() }; FRAMES_IN_FLIGHT],
+    }
 }
 
+/// The device must be idle
 pub fn destroy_resources(resources: &mut Resources, context: &InternalContext) {
+    for frame in 0..FRAMES_IN_FLIGHT {
+        resources.flush_deletion_queue(context, frame);
+    }
+    for pipeline in resources.pipelines.iter() {
+        destroy_pipeline(pipeline, &context.device);
+    }
+    for buffer in resources.buffers.iter() {
+        destroy_buffer(buffer, &context.device);
+    }
+    for texture in resources.textures.iter() {
+        destroy_texture(texture, &context.device);
+    }
+}
+
+/// Names the pipeline and its layouts after `handle`
+pub fn name_pipeline(context: &InternalContext, pipeline: &Pipeline, handle: PipelineHandle) {
+    set_object_name(context, pipeline.raw, &handle.to_string());
+    set_object_name(context, pipeline.layout, &format!("{} layout", handle));
+    for (set, layout) in pipeline.set_layouts.iter().enumerate() {
+        set_object_name(context, *layout, &format!("{} set {} layout", handle, set));
+    }
+}
+
+pub fn destroy_pipeline(pipeline: &Pipeline, device: &ash::Device) {
     unsafe {
-        for pip in resources.pipelines.iter() {
-            context.device.destroy_pipeline(*pip, None);
+        device.destroy_pipeline(pipeline.raw, None);
+        device.destroy_pipeline_layout(pipeline.layout, None);
+    }
+    destroy_set_layouts(device, &pipeline.set_layouts);
+}
+
+/// Creates the pipeline layout together with one set layout per descriptor set.
+/// Sets skipped by the shaders get an empty layout
+pub fn create_pipeline_layout(
+    device: &ash::Device,
+    interface: &PipelineInterface,
+) -> Result<(vk::PipelineLayout, Vec<vk::DescriptorSetLayout>), VulkanError> {
+    let set_count = interface.bindings.iter().map(|b| b.set + 1).max().unwrap_or(0);
+    let mut set_layouts = Vec::with_capacity(set_count as usize);
+    for set in 0..set_count {
+        let layout_bindings: Vec<_> = interface
+            .bindings
+            .iter()
+            .filter(|b| b.set == set)
+            .map(|b| {
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(b.binding)
+                    .descriptor_type(b.kind.to_vk())
+                    .descriptor_count(b.count)
+                    .stage_flags(b.stages)
+            })
+            .collect();
+        let create_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&layout_bindings);
+        match unsafe { device.create_descriptor_set_layout(&create_info, None) } {
+            Ok(layout) => set_layouts.push(layout),
+            Err(_) => {
+                destroy_set_layouts(device, &set_layouts);
+                return Err(VulkanError::ResourceCreationFailed);
+            }
+        }
+    }
+
+    let push_constant_ranges = [vk::PushConstantRange {
+        stage_flags: interface.push_constant_stages,
+        offset: 0,
+        size: interface.push_constant_size,
+    }];
+    let push_constant_ranges =
+        if interface.push_constant_size > 0 { &push_constant_ranges[..] } else { &[] };
+    let layout_info = vk::PipelineLayoutCreateInfo::default()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+    match unsafe { device.create_pipeline_layout(&layout_info, None) } {
+        Ok(layout) => Ok((layout, set_layouts)),
+        Err(_) => {
+            destroy_set_layouts(device, &set_layouts);
+            Err(VulkanError::ResourceCreationFailed)
+        }
+    }
+}
+
+pub fn destroy_set_layouts(device: &ash::Device, set_layouts: &[vk::DescriptorSetLayout]) {
+    for set_layout in set_layouts {
+        unsafe { device.destroy_descriptor_set_layout(*set_layout, None) };
+    }
+}
+
+pub fn create_shader_module(
+    device: &ash::Device,
+    source: &[u8],
+) -> Result<vk::ShaderModule, VulkanError> {
+    let code = ash::util::read_spv(&mut Cursor::new(source)).map_err(|e| {
+        utils::error(format!("Invalid SPIR-V: {}", e));
+        VulkanError::ResourceCreationFailed
+    })?;
+    let create_info = vk::ShaderModuleCreateInfo::default().code(&code);
+    unsafe {
+        device
+            .create_shader_module(&create_info, None)
+            .map_err(|_| VulkanError::ResourceCreationFailed)
+    }
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+fn build_graphics_pipeline(
+    context: &InternalContext,
+    pass: &Pass,
+    config: &PipelineConfig,
+) -> Result<Pipeline, VulkanError> {
+    let vertex = reflect_shader(config.vertext_shader_source)?;
+    let fragment = reflect_shader(config.fragment_shader_source)?;
+    check_stage(&vertex, vk::ShaderStageFlags::VERTEX)?;
+    check_stage(&fragment, vk::ShaderStageFlags::FRAGMENT)?;
+    check_vertex_inputs(&vertex, config.vertex_layouts)?;
+    check_stage_interface(&vertex, &fragment)?;
+    check_fragment_outputs(&fragment, pass.color_attachment_count)?;
+    check_pipeline_state(&config.state, pass.color_attachment_count, &context.features)?;
+    if config.state.stencil.is_some() && pass.depth_format == vk::Format::D32_SFLOAT {
+        return Err(VulkanError::FeatureNotSupported("stencil attachment"));
+    }
+    let interface = merge_stages(&[&vertex, &fragment])?;
+
+    let device = &context.device;
+    let vertex_module = create_shader_module(device, config.vertext_shader_source)?;
+    let fragment_module = match create_shader_module(device, config.fragment_shader_source) {
+        Ok(module) => module,
+        Err(e) => {
+            unsafe { device.destroy_shader_module(vertex_module, None) };
+            return Err(e);
+        }
+    };
+
+    let result = create_graphics_pipeline(
+        device,
+        context.pipeline_cache,
+        pass,
+        vertex_module,
+        fragment_module,
+        config,
+        interface,
+    );
+    unsafe {
+        device.destroy_shader_module(vertex_module, None);
+        device.destroy_shader_module(fragment_module, None);
+    }
+    result
+}
+
+fn create_graphics_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    pass: &Pass,
+    vertex_module: vk::ShaderModule,
+    fragment_module: vk::ShaderModule,
+    config: &PipelineConfig,
+    interface: PipelineInterface,
+) -> Result<Pipeline, VulkanError> {
+    let (layout, set_layouts) = create_pipeline_layout(device, &interface)?;
+
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vertex_module)
+            .name(SHADER_ENTRY),
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(fragment_module)
+            .name(SHADER_ENTRY),
+    ];
+    let state = &config.state;
+    let (vertex_bindings, vertex_attributes) = vertex_input_descriptions(config.vertex_layouts);
+    let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(&vertex_bindings)
+        .vertex_attribute_descriptions(&vertex_attributes);
+    let input_assembly =
+        vk::PipelineInputAssemblyStateCreateInfo::default().topology(state.topology.to_vk());
+    let viewport =
+        vk::PipelineViewportStateCreateInfo::default().viewport_count(1).scissor_count(1);
+    let rasterization = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(state.polygon_mode.to_vk())
+        .cull_mode(state.cull_mode.to_vk())
+        .front_face(state.front_face.to_vk())
+        .depth_bias_enable(state.dynamic_states.contains(&DynamicState::DepthBias))
+        .line_width(1.0);
+    let multisample = vk::PipelineMultisampleStateCreateInfo::default()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+    let stencil = state
+        .stencil
+        .unwrap_or(StencilState { front: StencilFace::default(), back: StencilFace::default() });
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(state.depth.test)
+        .depth_write_enable(state.depth.write)
+        .depth_compare_op(state.depth.compare.to_vk())
+        .stencil_test_enable(state.stencil.is_some())
+        .front(stencil.front.to_vk())
+        .back(stencil.back.to_vk());
+    let blend_attachments: Vec<_> = if state.color_attachments.is_empty() {
+        let opaque = ColorAttachmentState::default().to_vk();
+        vec![opaque; pass.color_attachment_count as usize]
+    } else {
+        state.color_attachments.iter().map(|attachment| attachment.to_vk()).collect()
+    };
+    let color_blend =
+        vk::PipelineColorBlendStateCreateInfo::default().attachments(&blend_attachments);
+    let dynamic_states: Vec<_> = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]
+        .into_iter()
+        .chain(state.dynamic_states.iter().map(|dynamic| dynamic.to_vk()))
+        .collect();
+    let dynamic = vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    let create_info = vk::GraphicsPipelineCreateInfo::default()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport)
+        .rasterization_state(&rasterization)
+        .multisample_state(&multisample)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&color_blend)
+        .dynamic_state(&dynamic)
+        .layout(layout)
+        .render_pass(pass.raw)
+        .subpass(0);
+
+    let result = unsafe { device.create_graphics_pipelines(cache, &[create_info], None) };
+    match result {
+        Ok(pipelines) => Ok(Pipeline {
+            raw: pipelines[0],
+            layout,
+            bind_point: vk::PipelineBindPoint::GRAPHICS,
+            set_layouts,
+            interface,
+            graphics: Some(GraphicsPipelineDesc::new(config)),
+        }),
+        Err(_) => {
+            unsafe { device.destroy_pipeline_layout(layout, None) };
+            destroy_set_layouts(device, &set_layouts);
+            Err(VulkanError::ResourceCreationFailed)
         }
     }
 }
@@ -0,0 +1,798 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use ash::vk;
+
+use super::{BindingType, VulkanError};
+
+const SPIRV_MAGIC: u32 = 0x07230203;
+/// Deepest type nesting followed, deeper or self-referencing types are rejected
+const MAX_TYPE_DEPTH: u32 = 32;
+
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+/// Everything about a shader module that has to match the pipeline layout and state
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub stage: vk::ShaderStageFlags,
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+    pub bindings: Vec<DescriptorBinding>,
+    pub push_constant_size: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct InterfaceVariable {
+    pub location: u32,
+    pub name: String,
+    /// `None` for types that can not be described as a single vector, e.g. structs
+    pub ty: Option<ValueType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueType {
+    pub kind: ScalarKind,
+    pub width: u32,
+    pub components: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarKind {
+    Float,
+    Int,
+    Uint,
+    Bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub kind: BindingType,
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    pub name: String,
+}
+
+/// Descriptor bindings and push constants of all stages of a pipeline
+#[derive(Debug, Clone, Default)]
+pub struct PipelineInterface {
+    /// Sorted by set and binding
+    pub bindings: Vec<DescriptorBinding>,
+    pub push_constant_size: u32,
+    pub push_constant_stages: vk::ShaderStageFlags,
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+#[derive(Debug, Default, Clone)]
+struct Decorations {
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    built_in: bool,
+    buffer_block: bool,
+    array_stride: Option<u32>,
+}
+
+#[derive(Default)]
+struct Module {
+    stage: vk::ShaderStageFlags,
+    names: HashMap<u32, String>,
+    decorations: HashMap<u32, Decorations>,
+    member_offsets: HashMap<(u32, u32), u32>,
+    member_matrix_strides: HashMap<(u32, u32), u32>,
+    built_in_structs: Vec<u32>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (scalar, prefix) = match self.kind {
+            ScalarKind::Float if self.width == 64 => ("double", "d"),
+            ScalarKind::Float => ("float", ""),
+            ScalarKind::Int => ("int", "i"),
+            ScalarKind::Uint => ("uint", "u"),
+            ScalarKind::Bool => ("bool", "b"),
+        };
+        if self.components == 1 {
+            write!(f, "{scalar}")
+        } else {
+            write!(f, "{prefix}vec{}", self.components)
+        }
+    }
+}
+
+impl std::fmt::Display for BindingType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BindingType::UniformBuffer => "uniform buffer",
+            BindingType::StorageBuffer => "storage buffer",
+            BindingType::StorageImage => "storage image",
+            BindingType::SampledImage => "sampled image",
+            BindingType::Sampler => "sampler",
+            BindingType::CombinedImageSampler => "combined image sampler",
+        };
+        write!(f, "{name}")
+    }
+}
+
+pub fn reflect_shader(source: &[u8]) -> Result<ShaderReflection, VulkanError> {
+    let words = ash::util::read_spv(&mut Cursor::new(source))
+        .map_err(|e| VulkanError::InvalidShader(format!("Invalid SPIR-V: {e}")))?;
+    if words.len() < 5 || words[0] != SPIRV_MAGIC {
+        return Err(VulkanError::InvalidShader("Missing SPIR-V header".to_string()));
+    }
+
+    let mut module = Module::default();
+    let mut offset = 5;
+    while offset < words.len() {
+        let word_count = (words[offset] >> 16) as usize;
+        let opcode = words[offset] & 0xffff;
+        if word_count == 0 || offset + word_count > words.len() {
+            return Err(VulkanError::InvalidShader("Truncated SPIR-V instruction".to_string()));
+        }
+        module.parse_instruction(opcode, &words[offset + 1..offset + word_count]);
+        offset += word_count;
+    }
+    if module.stage.is_empty() {
+        return Err(VulkanError::InvalidShader("SPIR-V has no supported entry point".to_string()));
+    }
+    module.reflect()
+}
+
+/// Merges the resources of all stages, a binding shared by several stages
+/// has to have the same type and count in each of them. Samplers and sampled images
+/// are rejected, textures can only be bound as storage images
+pub fn merge_stages(stages: &[&ShaderReflection]) -> Result<PipelineInterface, VulkanError> {
+    let mut interface = PipelineInterface::default();
+    for stage in stages {
+        for binding in stage.bindings.iter() {
+            if !binding.kind.is_supported() {
+                return Err(VulkanError::InvalidShader(format!(
+                    "Set {} binding {} `{}` in {:?} shader is a {}, only uniform buffers, storage buffers and storage images can be bound",
+                    binding.set, binding.binding, binding.name, stage.stage, binding.kind
+                )));
+            }
+            let existing = interface
+                .bindings
+                .iter_mut()
+                .find(|b| b.set == binding.set && b.binding == binding.binding);
+            match existing {
+                Some(existing)
+                    if existing.kind != binding.kind || existing.count != binding.count =>
+                {
+                    return Err(VulkanError::PipelineLayoutMismatch(format!(
+                        "Set {} binding {} is {} x{} `{}` in {:?} but {} x{} `{}` in {:?}",
+                        binding.set,
+                        binding.binding,
+                        existing.kind,
+                        existing.count,
+                        existing.name,
+                        existing.stages,
+                        binding.kind,
+                        binding.count,
+                        binding.name,
+                        stage.stage
+                    )));
+                }
+                Some(existing) => existing.stages |= stage.stage,
+                None => interface.bindings.push(binding.clone()),
+            }
+        }
+        if stage.push_constant_size > 0 {
+            interface.push_constant_size =
+                interface.push_constant_size.max(stage.push_constant_size);
+            interface.push_constant_stages |= stage.stage;
+        }
+    }
+    interface.bindings.sort_by_key(|b| (b.set, b.binding));
+    Ok(interface)
+}
+
+/// Checks that every fragment input is written by the vertex shader with the same type
+pub fn check_stage_interface(
+    vertex: &ShaderReflection,
+    fragment: &ShaderReflection,
+) -> Result<(), VulkanError> {
+    for input in fragment.inputs.iter() {
+        let Some(output) = vertex.outputs.iter().find(|o| o.location == input.location) else {
+            return Err(VulkanError::PipelineLayoutMismatch(format!(
+                "Fragment input `{}` at location {} is not written by the vertex shader",
+                input.name, input.location
+            )));
+        };
+        if let (Some(input_ty), Some(output_ty)) = (input.ty, output.ty) {
+            if input_ty != output_ty {
+                return Err(VulkanError::PipelineLayoutMismatch(format!(
+                    "Location {} is written as {} `{}` by the vertex shader but read as {} `{}` by the fragment shader",
+                    input.location, output_ty, output.name, input_ty, input.name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the fragment shader only writes attachments the render pass has
+pub fn check_fragment_outputs(
+    fragment: &ShaderReflection,
+    color_attachment_count: u32,
+) -> Result<(), VulkanError> {
+    match fragment.outputs.iter().find(|o| o.location >= color_attachment_count) {
+        Some(output) => Err(VulkanError::PipelineLayoutMismatch(format!(
+            "Fragment output `{}` at location {} has no matching attachment, the pass has {} color attachment(s)",
+            output.name, output.location, color_attachment_count
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the shader module was compiled for the stage it is used as
+pub fn check_stage(
+    reflection: &ShaderReflection,
+    stage: vk::ShaderStageFlags,
+) -> Result<(), VulkanError> {
+    if reflection.stage != stage {
+        return Err(VulkanError::PipelineLayoutMismatch(format!(
+            "Expected a {:?} shader but got a {:?} shader",
+            stage, reflection.stage
+        )));
+    }
+    Ok(())
+}
+
+// PRIVATE FUNCTIONS ==========================================================================
+impl Module {
+    fn parse_instruction(&mut self, opcode: u32, operands: &[u32]) {
+        let operand = |index: usize| operands.get(index).copied().unwrap_or(0);
+        match opcode {
+            OP_NAME => {
+                self.names.insert(operand(0), parse_string(operands.get(1..).unwrap_or_default()));
+            }
+            OP_ENTRY_POINT if self.stage.is_empty() => {
+                self.stage = match operand(0) {
+                    0 => vk::ShaderStageFlags::VERTEX,
+                    4 => vk::ShaderStageFlags::FRAGMENT,
+                    5 => vk::ShaderStageFlags::COMPUTE,
+                    _ => vk::ShaderStageFlags::empty(),
+                };
+            }
+            OP_TYPE_BOOL => {
+                self.types.insert(operand(0), Type::Bool);
+            }
+            OP_TYPE_INT => {
+                let ty = Type::Int { width: operand(1), signed: operand(2) == 1 };
+                self.types.insert(operand(0), ty);
+            }
+            OP_TYPE_FLOAT => {
+                self.types.insert(operand(0), Type::Float { width: operand(1) });
+            }
+            OP_TYPE_VECTOR => {
+                let ty = Type::Vector { component: operand(1), count: operand(2) };
+                self.types.insert(operand(0), ty);
+            }
+            OP_TYPE_MATRIX => {
+                let ty = Type::Matrix { column: operand(1), count: operand(2) };
+                self.types.insert(operand(0), ty);
+            }
+            OP_TYPE_IMAGE => {
+                self.types.insert(operand(0), Type::Image { sampled: operand(6) });
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(0), Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0), Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                let length = self.constants.get(&operand(2)).copied().unwrap_or(1);
+                self.types.insert(operand(0), Type::Array { element: operand(1), length });
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(operand(0), Type::RuntimeArray);
+            }
+            OP_TYPE_STRUCT => {
+                let members = operands.get(1..).unwrap_or_default().to_vec();
+                self.types.insert(operand(0), Type::Struct { members });
+            }
+            OP_TYPE_POINTER => {
+                self.types.insert(operand(0), Type::Pointer { pointee: operand(2) });
+            }
+            OP_CONSTANT => {
+                self.constants.insert(operand(1), operand(2));
+            }
+            OP_VARIABLE => {
+                self.variables.push((operand(1), operand(0), operand(2)));
+            }
+            OP_DECORATE => {
+                let decorations = self.decorations.entry(operand(0)).or_default();
+                match operand(1) {
+                    DECORATION_LOCATION => decorations.location = Some(operand(2)),
+                    DECORATION_BINDING => decorations.binding = Some(operand(2)),
+                    DECORATION_DESCRIPTOR_SET => decorations.set = Some(operand(2)),
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = Some(operand(2)),
+                    _ => {}
+                }
+            }
+            OP_MEMBER_DECORATE => {
+                let member = (operand(0), operand(1));
+                match operand(2) {
+                    DECORATION_OFFSET => {
+                        self.member_offsets.insert(member, operand(3));
+                    }
+                    DECORATION_MATRIX_STRIDE => {
+                        self.member_matrix_strides.insert(member, operand(3));
+                    }
+                    DECORATION_BUILT_IN => self.built_in_structs.push(operand(0)),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn reflect(&self) -> Result<ShaderReflection, VulkanError> {
+        let mut reflection = ShaderReflection {
+            stage: self.stage,
+            inputs: vec![],
+            outputs: vec![],
+            bindings: vec![],
+            push_constant_size: 0,
+        };
+
+        for &(id, pointer_type, storage_class) in self.variables.iter() {
+            let Some(Type::Pointer { pointee }) = self.types.get(&pointer_type) else {
+                continue;
+            };
+            let pointee = *pointee;
+            let decorations = self.decorations.get(&id).cloned().unwrap_or_default();
+            let name = self.name_of(id, pointee);
+
+            match storage_class {
+                STORAGE_INPUT | STORAGE_OUTPUT => {
+                    if decorations.built_in || self.built_in_structs.contains(&pointee) {
+                        continue;
+                    }
+                    let Some(location) = decorations.location else {
+                        continue;
+                    };
+                    let variable =
+                        InterfaceVariable { location, name, ty: self.value_type(pointee, 0) };
+                    if storage_class == STORAGE_INPUT {
+                        reflection.inputs.push(variable);
+                    } else {
+                        reflection.outputs.push(variable);
+                    }
+                }
+                STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                    let (kind, count) =
+                        self.binding_type(pointee, storage_class, 0).ok_or_else(|| {
+                            VulkanError::InvalidShader(format!(
+                                "Unsupported resource type or array size of `{name}` in {:?} shader",
+                                self.stage
+                            ))
+                        })?;
+                    reflection.bindings.push(DescriptorBinding {
+                        set: decorations.set.unwrap_or(0),
+                        binding: decorations.binding.unwrap_or(0),
+                        kind,
+                        count,
+                        stages: self.stage,
+                        name,
+                    });
+                }
+                STORAGE_PUSH_CONSTANT => {
+                    let size = self.size_of(pointee, 0).ok_or_else(|| {
+                        VulkanError::InvalidShader(format!(
+                            "Size of push constant `{name}` in {:?} shader overflows or its type nests too deep",
+                            self.stage
+                        ))
+                    })?;
+                    reflection.push_constant_size = reflection.push_constant_size.max(size);
+                }
+                _ => {}
+            }
+        }
+
+        reflection.inputs.sort_by_key(|v| v.location);
+        reflection.outputs.sort_by_key(|v| v.location);
+        reflection.bindings.sort_by_key(|b| (b.set, b.binding));
+        Ok(reflection)
+    }
+
+    fn name_of(&self, id: u32, ty: u32) -> String {
+        match self.names.get(&id) {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => self.names.get(&ty).cloned().unwrap_or_else(|| format!("%{id}")),
+        }
+    }
+
+    fn value_type(&self, id: u32, depth: u32) -> Option<ValueType> {
+        if depth > MAX_TYPE_DEPTH {
+            return None;
+        }
+        match self.types.get(&id)? {
+            Type::Bool => Some(ValueType { kind: ScalarKind::Bool, width: 32, components: 1 }),
+            Type::Int { width, signed } => Some(ValueType {
+                kind: if *signed { ScalarKind::Int } else { ScalarKind::Uint },
+                width: *width,
+                components: 1,
+            }),
+            Type::Float { width } => {
+                Some(ValueType { kind: ScalarKind::Float, width: *width, components: 1 })
+            }
+            Type::Vector { component, count } => {
+                let scalar = self.value_type(*component, depth + 1)?;
+                Some(ValueType { components: *count, ..scalar })
+            }
+            _ => None,
+        }
+    }
+
+    /// `None` for unsupported types and counts that overflow
+    fn binding_type(&self, id: u32, storage_class: u32, depth: u32) -> Option<(BindingType, u32)> {
+        if depth > MAX_TYPE_DEPTH {
+            return None;
+        }
+        match self.types.get(&id)? {
+            Type::Array { element, length } => {
+                let (kind, count) = self.binding_type(*element, storage_class, depth + 1)?;
+                Some((kind, count.checked_mul(*length)?))
+            }
+            Type::Image { sampled: 2 } => Some((BindingType::StorageImage, 1)),
+            Type::Image { .. } => Some((BindingType::SampledImage, 1)),
+            Type::Sampler => Some((BindingType::Sampler, 1)),
+            Type::SampledImage => Some((BindingType::CombinedImageSampler, 1)),
+            Type::Struct { .. } => {
+                let decorations = self.decorations.get(&id).cloned().unwrap_or_default();
+                if storage_class == STORAGE_STORAGE_BUFFER || decorations.buffer_block {
+                    Some((BindingType::StorageBuffer, 1))
+                } else {
+                    Some((BindingType::UniformBuffer, 1))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// `None` if the size overflows or the type nests too deep
+    fn size_of(&self, id: u32, depth: u32) -> Option<u32> {
+        if depth > MAX_TYPE_DEPTH {
+            return None;
+        }
+        let size = match self.types.get(&id) {
+            Some(Type::Bool) => 4,
+            Some(Type::Int { width, .. }) | Some(Type::Float { width }) => width / 8,
+            Some(Type::Vector { component, count }) => {
+                self.size_of(*component, depth + 1)?.checked_mul(*count)?
+            }
+            Some(Type::Matrix { column, count }) => {
+                self.size_of(*column, depth + 1)?.checked_mul(*count)?
+            }
+            Some(Type::Array { element, length }) => {
+                let stride = match self.decorations.get(&id).and_then(|d| d.array_stride) {
+                    Some(stride) => stride,
+                    None => self.size_of(*element, depth + 1)?,
+                };
+                stride.checked_mul(*length)?
+            }
+            Some(Type::Struct { members }) => {
+                let mut size = 0;
+                for (index, member) in members.iter().enumerate() {
+                    let key = (id, index as u32);
+                    let offset = self.member_offsets.get(&key).copied().unwrap_or(0);
+                    let member_size =
+                        match (self.types.get(member), self.member_matrix_strides.get(&key)) {
+                            (Some(Type::Matrix { count, .. }), Some(stride)) => {
+                                count.checked_mul(*stride)?
+                            }
+                            _ => self.size_of(*member, depth + 1)?,
+                        };
+                    size = size.max(offset.checked_add(member_size)?);
+                }
+                size
+            }
+            _ => 0,
+        };
+        Some(size)
+    }
+}
+
+fn parse_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> =
+        words.iter().flat_map(|word| word.to_le_bytes()).take_while(|&byte| byte != 0).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXECUTION_MODEL_VERTEX: u32 = 0;
+    const DECORATION_BLOCK: u32 = 2;
+
+    fn instruction(words: &mut Vec<u32>, opcode: u32, operands: &[u32]) {
+        words.push(((operands.len() as u32 + 1) << 16) | opcode);
+        words.extend_from_slice(operands);
+    }
+
+    fn spirv(instructions: &[u32]) -> Vec<u8> {
+        let header = [SPIRV_MAGIC, 0x00010000, 0, 64, 0];
+        header.iter().chain(instructions).flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    /// Vertex shader with `vec2 position` at location 3 and a uniform block at set 1 binding 2
+    fn vertex_module() -> Vec<u32> {
+        let mut words = vec![];
+        instruction(&mut words, OP_ENTRY_POINT, &[EXECUTION_MODEL_VERTEX, 1, 0x6e69616d, 0]);
+        instruction(&mut words, OP_NAME, &[2, 0x69736f70, 0x6e6f6974, 0]);
+        instruction(&mut words, OP_DECORATE, &[2, DECORATION_LOCATION, 3]);
+        instruction(&mut words, OP_DECORATE, &[6, DECORATION_BLOCK]);
+        instruction(&mut words, OP_DECORATE, &[8, DECORATION_DESCRIPTOR_SET, 1]);
+        instruction(&mut words, OP_DECORATE, &[8, DECORATION_BINDING, 2]);
+        instruction(&mut words, OP_TYPE_FLOAT, &[3, 32]);
+        instruction(&mut words, OP_TYPE_VECTOR, &[4, 3, 2]);
+        instruction(&mut words, OP_TYPE_POINTER, &[5, STORAGE_INPUT, 4]);
+        instruction(&mut words, OP_VARIABLE, &[5, 2, STORAGE_INPUT]);
+        instruction(&mut words, OP_TYPE_STRUCT, &[6, 3]);
+        instruction(&mut words, OP_TYPE_POINTER, &[7, STORAGE_UNIFORM, 6]);
+        instruction(&mut words, OP_VARIABLE, &[7, 8, STORAGE_UNIFORM]);
+        words
+    }
+
+    fn stage(stage: vk::ShaderStageFlags) -> ShaderReflection {
+        ShaderReflection {
+            stage,
+            inputs: vec![],
+            outputs: vec![],
+            bindings: vec![],
+            push_constant_size: 0,
+        }
+    }
+
+    fn variable(location: u32, name: &str, kind: ScalarKind, components: u32) -> InterfaceVariable {
+        let ty = ValueType { kind, width: 32, components };
+        InterfaceVariable { location, name: name.to_string(), ty: Some(ty) }
+    }
+
+    fn binding(
+        set: u32,
+        binding: u32,
+        kind: BindingType,
+        stages: vk::ShaderStageFlags,
+        name: &str,
+    ) -> DescriptorBinding {
+        DescriptorBinding { set, binding, kind, count: 1, stages, name: name.to_string() }
+    }
+
+    fn message<T: std::fmt::Debug>(result: Result<T, VulkanError>) -> String {
+        match result {
+            Err(VulkanError::InvalidShader(message))
+            | Err(VulkanError::PipelineLayoutMismatch(message)) => message,
+            other => panic!("Expected a shader error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reflects_inputs_and_bindings() {
+        let reflection = reflect_shader(&spirv(&vertex_module())).unwrap();
+        assert_eq!(reflection.stage, vk::ShaderStageFlags::VERTEX);
+        assert_eq!(reflection.inputs.len(), 1);
+        assert_eq!(reflection.inputs[0].location, 3);
+        assert_eq!(reflection.inputs[0].name, "position");
+        let ty = ValueType { kind: ScalarKind::Float, width: 32, components: 2 };
+        assert_eq!(reflection.inputs[0].ty, Some(ty));
+        assert_eq!(reflection.bindings.len(), 1);
+        let binding = &reflection.bindings[0];
+        assert_eq!((binding.set, binding.binding), (1, 2));
+        assert_eq!(binding.kind, BindingType::UniformBuffer);
+    }
+
+    #[test]
+    fn ignores_truncated_op_name() {
+        let mut words = vertex_module();
+        instruction(&mut words, OP_NAME, &[]);
+        assert!(reflect_shader(&spirv(&words)).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_modules() {
+        let header_only = spirv(&[]);
+        assert_eq!(message(reflect_shader(&header_only[..8])), "Missing SPIR-V header");
+        assert_eq!(message(reflect_shader(&header_only)), "SPIR-V has no supported entry point");
+
+        let mut words = vertex_module();
+        words.push((4 << 16) | OP_NAME);
+        assert_eq!(message(reflect_shader(&spirv(&words))), "Truncated SPIR-V instruction");
+    }
+
+    /// `vertex_module` plus `uint %21 = 0x10000` for array lengths
+    fn module_with_large_constant() -> Vec<u32> {
+        let mut words = vertex_module();
+        instruction(&mut words, OP_TYPE_INT, &[20, 32, 0]);
+        instruction(&mut words, OP_CONSTANT, &[20, 21, 0x10000]);
+        words
+    }
+
+    #[test]
+    fn rejects_oversized_types() {
+        let mut push_constant = module_with_large_constant();
+        instruction(&mut push_constant, OP_DECORATE, &[22, DECORATION_ARRAY_STRIDE, 0x10000]);
+        instruction(&mut push_constant, OP_TYPE_ARRAY, &[22, 3, 21]);
+        instruction(&mut push_constant, OP_TYPE_POINTER, &[23, STORAGE_PUSH_CONSTANT, 22]);
+        instruction(&mut push_constant, OP_VARIABLE, &[23, 24, STORAGE_PUSH_CONSTANT]);
+        assert_eq!(
+            message(reflect_shader(&spirv(&push_constant))),
+            "Size of push constant `%24` in VERTEX shader overflows or its type nests too deep"
+        );
+
+        let mut binding = module_with_large_constant();
+        instruction(&mut binding, OP_TYPE_ARRAY, &[22, 6, 21]);
+        instruction(&mut binding, OP_TYPE_ARRAY, &[23, 22, 21]);
+        instruction(&mut binding, OP_TYPE_POINTER, &[24, STORAGE_UNIFORM, 23]);
+        instruction(&mut binding, OP_VARIABLE, &[24, 25, STORAGE_UNIFORM]);
+        assert_eq!(
+            message(reflect_shader(&spirv(&binding))),
+            "Unsupported resource type or array size of `%25` in VERTEX shader"
+        );
+    }
+
+    #[test]
+    fn rejects_self_referencing_types() {
+        let mut push_constant = module_with_large_constant();
+        instruction(&mut push_constant, OP_TYPE_ARRAY, &[22, 22, 21]);
+        instruction(&mut push_constant, OP_TYPE_POINTER, &[23, STORAGE_PUSH_CONSTANT, 22]);
+        instruction(&mut push_constant, OP_VARIABLE, &[23, 24, STORAGE_PUSH_CONSTANT]);
+        assert_eq!(
+            message(reflect_shader(&spirv(&push_constant))),
+            "Size of push constant `%24` in VERTEX shader overflows or its type nests too deep"
+        );
+
+        let mut binding = module_with_large_constant();
+        instruction(&mut binding, OP_TYPE_ARRAY, &[22, 22, 21]);
+        instruction(&mut binding, OP_TYPE_POINTER, &[23, STORAGE_UNIFORM, 22]);
+        instruction(&mut binding, OP_VARIABLE, &[23, 24, STORAGE_UNIFORM]);
+        assert!(reflect_shader(&spirv(&binding)).is_err());
+
+        let mut input = module_with_large_constant();
+        instruction(&mut input, OP_DECORATE, &[24, DECORATION_LOCATION, 5]);
+        instruction(&mut input, OP_TYPE_VECTOR, &[22, 22, 2]);
+        instruction(&mut input, OP_TYPE_POINTER, &[23, STORAGE_INPUT, 22]);
+        instruction(&mut input, OP_VARIABLE, &[23, 24, STORAGE_INPUT]);
+        let reflection = reflect_shader(&spirv(&input)).unwrap();
+        assert_eq!(reflection.inputs[1].ty, None);
+    }
+
+    #[test]
+    fn merges_shared_bindings_and_push_constants() {
+        let mut vertex = stage(vk::ShaderStageFlags::VERTEX);
+        vertex.bindings.push(binding(0, 1, BindingType::StorageBuffer, vertex.stage, "lights"));
+        vertex.bindings.push(binding(0, 0, BindingType::UniformBuffer, vertex.stage, "camera"));
+        vertex.push_constant_size = 16;
+        let mut fragment = stage(vk::ShaderStageFlags::FRAGMENT);
+        fragment.bindings.push(binding(0, 0, BindingType::UniformBuffer, fragment.stage, "camera"));
+        fragment.push_constant_size = 32;
+
+        let interface = merge_stages(&[&vertex, &fragment]).unwrap();
+        let bindings: Vec<_> = interface.bindings.iter().map(|b| (b.binding, b.stages)).collect();
+        let both = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+        assert_eq!(bindings, [(0, both), (1, vk::ShaderStageFlags::VERTEX)]);
+        assert_eq!(interface.push_constant_size, 32);
+        assert_eq!(interface.push_constant_stages, both);
+    }
+
+    #[test]
+    fn rejects_mismatched_bindings() {
+        let mut vertex = stage(vk::ShaderStageFlags::VERTEX);
+        vertex.bindings.push(binding(0, 0, BindingType::UniformBuffer, vertex.stage, "camera"));
+        let mut fragment = stage(vk::ShaderStageFlags::FRAGMENT);
+        fragment.bindings.push(binding(0, 0, BindingType::StorageBuffer, fragment.stage, "lights"));
+
+        assert_eq!(
+            message(merge_stages(&[&vertex, &fragment])),
+            "Set 0 binding 0 is uniform buffer x1 `camera` in VERTEX but storage buffer x1 `lights` in FRAGMENT"
+        );
+    }
+
+    #[test]
+    fn rejects_samplers() {
+        let mut fragment = stage(vk::ShaderStageFlags::FRAGMENT);
+        fragment.bindings.push(binding(
+            1,
+            0,
+            BindingType::CombinedImageSampler,
+            fragment.stage,
+            "albedo",
+        ));
+
+        assert_eq!(
+            message(merge_stages(&[&fragment])),
+            "Set 1 binding 0 `albedo` in FRAGMENT shader is a combined image sampler, only uniform buffers, storage buffers and storage images can be bound"
+        );
+    }
+
+    #[test]
+    fn checks_stage_interface() {
+        let mut vertex = stage(vk::ShaderStageFlags::VERTEX);
+        vertex.outputs.push(variable(0, "out_color", ScalarKind::Float, 4));
+        let mut fragment = stage(vk::ShaderStageFlags::FRAGMENT);
+        fragment.inputs.push(variable(0, "color", ScalarKind::Float, 4));
+        assert!(check_stage_interface(&vertex, &fragment).is_ok());
+
+        fragment.inputs[0] = variable(0, "color", ScalarKind::Int, 3);
+        assert_eq!(
+            message(check_stage_interface(&vertex, &fragment)),
+            "Location 0 is written as vec4 `out_color` by the vertex shader but read as ivec3 `color` by the fragment shader"
+        );
+
+        fragment.inputs[0] = variable(1, "uv", ScalarKind::Float, 2);
+        assert_eq!(
+            message(check_stage_interface(&vertex, &fragment)),
+            "Fragment input `uv` at location 1 is not written by the vertex shader"
+        );
+    }
+
+    #[test]
+    fn checks_fragment_outputs() {
+        let mut fragment = stage(vk::ShaderStageFlags::FRAGMENT);
+        fragment.outputs.push(variable(0, "color", ScalarKind::Float, 4));
+        fragment.outputs.push(variable(1, "normal", ScalarKind::Float, 4));
+        assert!(check_fragment_outputs(&fragment, 2).is_ok());
+        assert_eq!(
+            message(check_fragment_outputs(&fragment, 1)),
+            "Fragment output `normal` at location 1 has no matching attachment, the pass has 1 color attachment(s)"
+        );
+    }
+}
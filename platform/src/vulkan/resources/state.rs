@@ -0,0 +1,477 @@
+use ash::vk;
+
+use super::VulkanError;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    #[default]
+    TriangleList,
+    TriangleStrip,
+    LineList,
+    LineStrip,
+    PointList,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    #[default]
+    None,
+    Front,
+    Back,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FrontFace {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonMode {
+    #[default]
+    Fill,
+    /// Needs the `fillModeNonSolid` device feature
+    Line,
+    /// Needs the `fillModeNonSolid` device feature
+    Point,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Never,
+    #[default]
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthState {
+    pub test: bool,
+    pub write: bool,
+    pub compare: CompareOp,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StencilOp {
+    #[default]
+    Keep,
+    Zero,
+    Replace,
+    IncrementClamp,
+    DecrementClamp,
+    Invert,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StencilFace {
+    pub fail: StencilOp,
+    pub pass: StencilOp,
+    pub depth_fail: StencilOp,
+    pub compare: CompareOp,
+    pub compare_mask: u32,
+    pub write_mask: u32,
+    /// Ignored when `DynamicState::StencilReference` is used
+    pub reference: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StencilState {
+    pub front: StencilFace,
+    pub back: StencilFace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    ConstantColor,
+    OneMinusConstantColor,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    #[default]
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendComponent {
+    pub src: BlendFactor,
+    pub dst: BlendFactor,
+    pub op: BlendOp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendState {
+    pub color: BlendComponent,
+    pub alpha: BlendComponent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorWrites {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+/// Blending of one color attachment, `blend: None` writes the fragment color as is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorAttachmentState {
+    pub blend: Option<BlendState>,
+    pub write_mask: ColorWrites,
+}
+
+/// State that is set while recording instead of being baked into the pipeline.
+/// Viewport and scissor are always dynamic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DynamicState {
+    BlendConstants,
+    StencilReference,
+    /// Also enables depth bias in the rasterizer
+    DepthBias,
+}
+
+/// Fixed function state of a graphics pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipelineState<'a> {
+    pub topology: Topology,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub polygon_mode: PolygonMode,
+    pub depth: DepthState,
+    pub stencil: Option<StencilState>,
+    /// One entry per color attachment of the pass, empty means opaque writes to all of them
+    pub color_attachments: &'a [ColorAttachmentState],
+    pub dynamic_states: &'a [DynamicState],
+}
+
+const ALPHA_BLENDED: [ColorAttachmentState; 1] =
+    [ColorAttachmentState { blend: Some(BlendState::ALPHA), write_mask: ColorWrites::ALL }];
+const ADDITIVE: [ColorAttachmentState; 1] =
+    [ColorAttachmentState { blend: Some(BlendState::ADDITIVE), write_mask: ColorWrites::ALL }];
+
+impl DepthState {
+    pub const DISABLED: Self = Self { test: false, write: false, compare: CompareOp::Always };
+    pub const READ_WRITE: Self = Self { test: true, write: true, compare: CompareOp::Less };
+    pub const READ_ONLY: Self = Self { test: true, write: false, compare: CompareOp::Less };
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self::READ_WRITE
+    }
+}
+
+impl Default for StencilFace {
+    fn default() -> Self {
+        Self {
+            fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            compare: CompareOp::Always,
+            compare_mask: 0xff,
+            write_mask: 0xff,
+            reference: 0,
+        }
+    }
+}
+
+impl BlendComponent {
+    pub const REPLACE: Self =
+        Self { src: BlendFactor::One, dst: BlendFactor::Zero, op: BlendOp::Add };
+}
+
+impl BlendState {
+    /// Straight alpha, `src * a + dst * (1 - a)`
+    pub const ALPHA: Self = Self {
+        color: BlendComponent {
+            src: BlendFactor::SrcAlpha,
+            dst: BlendFactor::OneMinusSrcAlpha,
+            op: BlendOp::Add,
+        },
+        alpha: BlendComponent {
+            src: BlendFactor::One,
+            dst: BlendFactor::OneMinusSrcAlpha,
+            op: BlendOp::Add,
+        },
+    };
+    /// Premultiplied alpha, `src + dst * (1 - a)`
+    pub const PREMULTIPLIED_ALPHA: Self = Self {
+        color: BlendComponent {
+            src: BlendFactor::One,
+            dst: BlendFactor::OneMinusSrcAlpha,
+            op: BlendOp::Add,
+        },
+        alpha: BlendComponent {
+            src: BlendFactor::One,
+            dst: BlendFactor::OneMinusSrcAlpha,
+            op: BlendOp::Add,
+        },
+    };
+    /// `src * a + dst`, for particles and lights
+    pub const ADDITIVE: Self = Self {
+        color: BlendComponent {
+            src: BlendFactor::SrcAlpha,
+            dst: BlendFactor::One,
+            op: BlendOp::Add,
+        },
+        alpha: BlendComponent { src: BlendFactor::One, dst: BlendFactor::One, op: BlendOp::Add },
+    };
+}
+
+impl ColorWrites {
+    pub const ALL: Self = Self { red: true, green: true, blue: true, alpha: true };
+    pub const NONE: Self = Self { red: false, green: false, blue: false, alpha: false };
+}
+
+impl Default for ColorWrites {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl Default for ColorAttachmentState {
+    fn default() -> Self {
+        Self { blend: None, write_mask: ColorWrites::ALL }
+    }
+}
+
+impl Default for PipelineState<'_> {
+    fn default() -> Self {
+        Self::opaque()
+    }
+}
+
+impl PipelineState<'_> {
+    /// Filled triangles with depth test and write, no culling and no blending
+    pub const fn opaque() -> Self {
+        Self {
+            topology: Topology::TriangleList,
+            cull_mode: CullMode::None,
+            front_face: FrontFace::Clockwise,
+            polygon_mode: PolygonMode::Fill,
+            depth: DepthState::READ_WRITE,
+            stencil: None,
+            color_attachments: &[],
+            dynamic_states: &[],
+        }
+    }
+
+    /// Depth tested but not written, meant to be drawn back to front after opaque geometry
+    pub const fn alpha_blended() -> Self {
+        Self { depth: DepthState::READ_ONLY, color_attachments: &ALPHA_BLENDED, ..Self::opaque() }
+    }
+
+    pub const fn additive() -> Self {
+        Self { depth: DepthState::READ_ONLY, color_attachments: &ADDITIVE, ..Self::opaque() }
+    }
+
+    pub const fn wireframe() -> Self {
+        Self { polygon_mode: PolygonMode::Line, ..Self::opaque() }
+    }
+}
+
+impl Topology {
+    pub fn to_vk(self) -> vk::PrimitiveTopology {
+        match self {
+            Topology::TriangleList => vk::PrimitiveTopology::TRIANGLE_LIST,
+            Topology::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+            Topology::LineList => vk::PrimitiveTopology::LINE_LIST,
+            Topology::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+            Topology::PointList => vk::PrimitiveTopology::POINT_LIST,
+        }
+    }
+}
+
+impl CullMode {
+    pub fn to_vk(self) -> vk::CullModeFlags {
+        match self {
+            CullMode::None => vk::CullModeFlags::NONE,
+            CullMode::Front => vk::CullModeFlags::FRONT,
+            CullMode::Back => vk::CullModeFlags::BACK,
+        }
+    }
+}
+
+impl FrontFace {
+    pub fn to_vk(self) -> vk::FrontFace {
+        match self {
+            FrontFace::Clockwise => vk::FrontFace::CLOCKWISE,
+            FrontFace::CounterClockwise => vk::FrontFace::COUNTER_CLOCKWISE,
+        }
+    }
+}
+
+impl PolygonMode {
+    pub fn to_vk(self) -> vk::PolygonMode {
+        match self {
+            PolygonMode::Fill => vk::PolygonMode::FILL,
+            PolygonMode::Line => vk::PolygonMode::LINE,
+            PolygonMode::Point => vk::PolygonMode::POINT,
+        }
+    }
+}
+
+impl CompareOp {
+    pub fn to_vk(self) -> vk::CompareOp {
+        match self {
+            CompareOp::Never => vk::CompareOp::NEVER,
+            CompareOp::Less => vk::CompareOp::LESS,
+            CompareOp::Equal => vk::CompareOp::EQUAL,
+            CompareOp::LessOrEqual => vk::CompareOp::LESS_OR_EQUAL,
+            CompareOp::Greater => vk::CompareOp::GREATER,
+            CompareOp::NotEqual => vk::CompareOp::NOT_EQUAL,
+            CompareOp::GreaterOrEqual => vk::CompareOp::GREATER_OR_EQUAL,
+            CompareOp::Always => vk::CompareOp::ALWAYS,
+        }
+    }
+}
+
+impl StencilOp {
+    pub fn to_vk(self) -> vk::StencilOp {
+        match self {
+            StencilOp::Keep => vk::StencilOp::KEEP,
+            StencilOp::Zero => vk::StencilOp::ZERO,
+            StencilOp::Replace => vk::StencilOp::REPLACE,
+            StencilOp::IncrementClamp => vk::StencilOp::INCREMENT_AND_CLAMP,
+            StencilOp::DecrementClamp => vk::StencilOp::DECREMENT_AND_CLAMP,
+            StencilOp::Invert => vk::StencilOp::INVERT,
+            StencilOp::IncrementWrap => vk::StencilOp::INCREMENT_AND_WRAP,
+            StencilOp::DecrementWrap => vk::StencilOp::DECREMENT_AND_WRAP,
+        }
+    }
+}
+
+impl StencilFace {
+    pub fn to_vk(self) -> vk::StencilOpState {
+        vk::StencilOpState {
+            fail_op: self.fail.to_vk(),
+            pass_op: self.pass.to_vk(),
+            depth_fail_op: self.depth_fail.to_vk(),
+            compare_op: self.compare.to_vk(),
+            compare_mask: self.compare_mask,
+            write_mask: self.write_mask,
+            reference: self.reference,
+        }
+    }
+}
+
+impl BlendFactor {
+    pub fn to_vk(self) -> vk::BlendFactor {
+        match self {
+            BlendFactor::Zero => vk::BlendFactor::ZERO,
+            BlendFactor::One => vk::BlendFactor::ONE,
+            BlendFactor::SrcColor => vk::BlendFactor::SRC_COLOR,
+            BlendFactor::OneMinusSrcColor => vk::BlendFactor::ONE_MINUS_SRC_COLOR,
+            BlendFactor::DstColor => vk::BlendFactor::DST_COLOR,
+            BlendFactor::OneMinusDstColor => vk::BlendFactor::ONE_MINUS_DST_COLOR,
+            BlendFactor::SrcAlpha => vk::BlendFactor::SRC_ALPHA,
+            BlendFactor::OneMinusSrcAlpha => vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::DstAlpha => vk::BlendFactor::DST_ALPHA,
+            BlendFactor::OneMinusDstAlpha => vk::BlendFactor::ONE_MINUS_DST_ALPHA,
+            BlendFactor::ConstantColor => vk::BlendFactor::CONSTANT_COLOR,
+            BlendFactor::OneMinusConstantColor => vk::BlendFactor::ONE_MINUS_CONSTANT_COLOR,
+        }
+    }
+}
+
+impl BlendOp {
+    pub fn to_vk(self) -> vk::BlendOp {
+        match self {
+            BlendOp::Add => vk::BlendOp::ADD,
+            BlendOp::Subtract => vk::BlendOp::SUBTRACT,
+            BlendOp::ReverseSubtract => vk::BlendOp::REVERSE_SUBTRACT,
+            BlendOp::Min => vk::BlendOp::MIN,
+            BlendOp::Max => vk::BlendOp::MAX,
+        }
+    }
+}
+
+impl ColorWrites {
+    pub fn to_vk(self) -> vk::ColorComponentFlags {
+        let mut flags = vk::ColorComponentFlags::empty();
+        if self.red {
+            flags |= vk::ColorComponentFlags::R;
+        }
+        if self.green {
+            flags |= vk::ColorComponentFlags::G;
+        }
+        if self.blue {
+            flags |= vk::ColorComponentFlags::B;
+        }
+        if self.alpha {
+            flags |= vk::ColorComponentFlags::A;
+        }
+        flags
+    }
+}
+
+impl ColorAttachmentState {
+    pub fn to_vk(self) -> vk::PipelineColorBlendAttachmentState {
+        let state = vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(self.write_mask.to_vk());
+        match self.blend {
+            Some(blend) => state
+                .blend_enable(true)
+                .src_color_blend_factor(blend.color.src.to_vk())
+                .dst_color_blend_factor(blend.color.dst.to_vk())
+                .color_blend_op(blend.color.op.to_vk())
+                .src_alpha_blend_factor(blend.alpha.src.to_vk())
+                .dst_alpha_blend_factor(blend.alpha.dst.to_vk())
+                .alpha_blend_op(blend.alpha.op.to_vk()),
+            None => state,
+        }
+    }
+}
+
+impl DynamicState {
+    pub fn to_vk(self) -> vk::DynamicState {
+        match self {
+            DynamicState::BlendConstants => vk::DynamicState::BLEND_CONSTANTS,
+            DynamicState::StencilReference => vk::DynamicState::STENCIL_REFERENCE,
+            DynamicState::DepthBias => vk::DynamicState::DEPTH_BIAS,
+        }
+    }
+}
+
+/// Checks the state against the pass it is used with and the enabled device features
+pub fn check_pipeline_state(
+    state: &PipelineState,
+    color_attachment_count: u32,
+    features: &vk::PhysicalDeviceFeatures,
+) -> Result<(), VulkanError> {
+    if !state.color_attachments.is_empty()
+        && state.color_attachments.len() != color_attachment_count as usize
+    {
+        return Err(VulkanError::PipelineLayoutMismatch(format!(
+            "Pipeline state describes {} color attachment(s) but the pass has {}",
+            state.color_attachments.len(),
+            color_attachment_count
+        )));
+    }
+    if state.polygon_mode != PolygonMode::Fill && features.fill_mode_non_solid != vk::TRUE {
+        return Err(VulkanError::FeatureNotSupported("fillModeNonSolid"));
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use ash::vk;
+
+use super::{
+    allocate_memory, immediate_submit, set_object_name, Garbage, InternalContext, Resources,
+    TextureHandle, VulkanError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8Unorm,
+    Rgba16Float,
+    Rgba32Float,
+    R32Float,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextureConfig {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+}
+
+/// 2D image usable as a storage image, kept in `GENERAL` layout for its whole life
+pub struct Texture {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+}
+
+impl TextureFormat {
+    fn to_vk(self) -> vk::Format {
+        match self {
+            TextureFormat::Rgba8Unorm => vk::Format::R8G8B8A8_UNORM,
+            TextureFormat::Rgba16Float => vk::Format::R16G16B16A16_SFLOAT,
+            TextureFormat::Rgba32Float => vk::Format::R32G32B32A32_SFLOAT,
+            TextureFormat::R32Float => vk::Format::R32_SFLOAT,
+        }
+    }
+}
+
+impl Resources {
+    pub fn create_texture(
+        &mut self,
+        context: &InternalContext,
+        config: TextureConfig,
+    ) -> Result<TextureHandle, VulkanError> {
+        let format = config.format.to_vk();
+        let extent = vk::Extent2D { width: config.width, height: config.height };
+        let create_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D { width: extent.width, height: extent.height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(
+                vk::ImageUsageFlags::STORAGE
+                    | vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::TRANSFER_DST,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        let texture = unsafe {
+            let image = context
+                .device
+                .create_image(&create_info, None)
+                .map_err(|_| VulkanError::ResourceCreationFailed)?;
+            let requirements = context.device.get_image_memory_requirements(image);
+            let memory = allocate_memory(
+                &context.device,
+                &context.memory_properties,
+                requirements,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+            .inspect_err(|_| context.device.destroy_image(image, None))?;
+            context.device.bind_image_memory(image, memory, 0).expect("Bind image memory failed");
+
+            let view_info = vk::ImageViewCreateInfo::default()
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format)
+                .subresource_range(subresource_range)
+                .image(image);
+            let view =
+                context.device.create_image_view(&view_info, None).expect("Cant create image view");
+
+            immediate_submit(context, |command_buffer| {
+                let barrier = vk::ImageMemoryBarrier::default()
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(vk::ImageLayout::GENERAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(image)
+                    .subresource_range(subresource_range);
+                context.device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[barrier],
+                );
+            });
+
+            Texture { image, memory, view }
+        };
+
+        let (image, memory, view) = (texture.image, texture.memory, texture.view);
+        let handle = self.textures.insert(texture);
+        set_object_name(context, image, &handle.to_string());
+        set_object_name(context, memory, &format!("{} memory", handle));
+        set_object_name(context, view, &format!("{} view", handle));
+        Ok(handle)
+    }
+
+    pub fn get_texture(&self, handle: TextureHandle) -> Result<&Texture, VulkanError> {
+        self.textures.get(handle).ok_or(VulkanError::TextureNotFound)
+    }
+
+    /// Same as `destroy_pipeline` for textures
+    pub fn destroy_texture(
+        &mut self,
+        context: &InternalContext,
+        handle: TextureHandle,
+    ) -> Result<(), VulkanError> {
+        let texture = self.textures.remove(handle).ok_or(VulkanError::TextureNotFound)?;
+        self.defer_destroy(context, Garbage::Texture(texture));
+        Ok(())
+    }
+}
+
+pub fn destroy_texture(texture: &Texture, device: &ash::Device) {
+    unsafe {
+        device.destroy_image_view(texture.view, None);
+        device.destroy_image(texture.image, None);
+        device.free_memory(texture.memory, None);
+    }
+}
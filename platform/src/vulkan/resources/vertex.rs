@@ -0,0 +1,316 @@
+use ash::vk;
+
+use super::{ScalarKind, ShaderReflection, VulkanError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexFormat {
+    Float,
+    Float2,
+    Float3,
+    Float4,
+    Int,
+    Int2,
+    Int3,
+    Int4,
+    Uint,
+    Uint2,
+    Uint3,
+    Uint4,
+    /// Four bytes normalized to `0.0..=1.0`, read as `vec4` in the shader
+    Unorm8x4,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VertexRate {
+    #[default]
+    Vertex,
+    Instance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub format: VertexFormat,
+    /// Byte offset inside one element of the buffer
+    pub offset: u32,
+}
+
+/// Layout of one vertex buffer binding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLayout {
+    pub stride: u32,
+    pub rate: VertexRate,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+/// Implemented with `impl_vertex!` for `#[repr(C)]` structs stored in vertex buffers
+pub trait Vertex {
+    /// Attributes in field order with locations starting at 0
+    fn attributes() -> Vec<VertexAttribute>;
+}
+
+/// Rust types that can be used as vertex struct fields
+pub trait VertexAttributeType {
+    const FORMAT: VertexFormat;
+}
+
+/// Implements `Vertex` for a `#[repr(C)]` struct, listed fields get consecutive locations
+/// and their format from the field type.
+///
+/// ```ignore
+/// #[repr(C)]
+/// struct MeshVertex {
+///     position: [f32; 3],
+///     color: [u8; 4],
+/// }
+/// impl_vertex!(MeshVertex { position, color });
+/// ```
+#[macro_export]
+macro_rules! impl_vertex {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::vulkan::Vertex for $ty {
+            fn attributes() -> Vec<$crate::vulkan::VertexAttribute> {
+                let mut attributes = vec![];
+                $(
+                    attributes.push($crate::vulkan::VertexAttribute {
+                        location: attributes.len() as u32,
+                        format: $crate::vulkan::field_format(|vertex: &$ty| &vertex.$field),
+                        offset: ::std::mem::offset_of!($ty, $field) as u32,
+                    });
+                )*
+                attributes
+            }
+        }
+    };
+}
+
+/// Used by `impl_vertex!` to get the format of a field from its type
+#[doc(hidden)]
+pub fn field_format<T, F: VertexAttributeType>(_field: fn(&T) -> &F) -> VertexFormat {
+    F::FORMAT
+}
+
+macro_rules! attribute_type {
+    ($($ty:ty => $format:ident),* $(,)?) => {
+        $(
+            impl VertexAttributeType for $ty {
+                const FORMAT: VertexFormat = VertexFormat::$format;
+            }
+        )*
+    };
+}
+
+attribute_type! {
+    f32 => Float,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    i32 => Int,
+    [i32; 2] => Int2,
+    [i32; 3] => Int3,
+    [i32; 4] => Int4,
+    u32 => Uint,
+    [u32; 2] => Uint2,
+    [u32; 3] => Uint3,
+    [u32; 4] => Uint4,
+    [u8; 4] => Unorm8x4,
+}
+
+impl VertexFormat {
+    pub fn to_vk(self) -> vk::Format {
+        match self {
+            VertexFormat::Float => vk::Format::R32_SFLOAT,
+            VertexFormat::Float2 => vk::Format::R32G32_SFLOAT,
+            VertexFormat::Float3 => vk::Format::R32G32B32_SFLOAT,
+            VertexFormat::Float4 => vk::Format::R32G32B32A32_SFLOAT,
+            VertexFormat::Int => vk::Format::R32_SINT,
+            VertexFormat::Int2 => vk::Format::R32G32_SINT,
+            VertexFormat::Int3 => vk::Format::R32G32B32_SINT,
+            VertexFormat::Int4 => vk::Format::R32G32B32A32_SINT,
+            VertexFormat::Uint => vk::Format::R32_UINT,
+            VertexFormat::Uint2 => vk::Format::R32G32_UINT,
+            VertexFormat::Uint3 => vk::Format::R32G32B32_UINT,
+            VertexFormat::Uint4 => vk::Format::R32G32B32A32_UINT,
+            VertexFormat::Unorm8x4 => vk::Format::R8G8B8A8_UNORM,
+        }
+    }
+
+    fn scalar_kind(self) -> ScalarKind {
+        match self {
+            VertexFormat::Int | VertexFormat::Int2 | VertexFormat::Int3 | VertexFormat::Int4 => {
+                ScalarKind::Int
+            }
+            VertexFormat::Uint
+            | VertexFormat::Uint2
+            | VertexFormat::Uint3
+            | VertexFormat::Uint4 => ScalarKind::Uint,
+            _ => ScalarKind::Float,
+        }
+    }
+}
+
+impl VertexRate {
+    fn to_vk(self) -> vk::VertexInputRate {
+        match self {
+            VertexRate::Vertex => vk::VertexInputRate::VERTEX,
+            VertexRate::Instance => vk::VertexInputRate::INSTANCE,
+        }
+    }
+}
+
+impl VertexLayout {
+    pub fn of<V: Vertex>(rate: VertexRate) -> Self {
+        Self { stride: std::mem::size_of::<V>() as u32, rate, attributes: V::attributes() }
+    }
+
+    /// Shifts all locations, e.g. for per-instance data following the per-vertex attributes
+    pub fn with_base_location(mut self, base: u32) -> Self {
+        for attribute in self.attributes.iter_mut() {
+            attribute.location += base;
+        }
+        self
+    }
+}
+
+/// Vertex input state for `layouts`, binding `i` is described by `layouts[i]`
+pub fn vertex_input_descriptions(
+    layouts: &[VertexLayout],
+) -> (Vec<vk::VertexInputBindingDescription>, Vec<vk::VertexInputAttributeDescription>) {
+    let bindings = layouts
+        .iter()
+        .enumerate()
+        .map(|(binding, layout)| vk::VertexInputBindingDescription {
+            binding: binding as u32,
+            stride: layout.stride,
+            input_rate: layout.rate.to_vk(),
+        })
+        .collect();
+    let attributes = layouts
+        .iter()
+        .enumerate()
+        .flat_map(|(binding, layout)| {
+            layout.attributes.iter().map(move |attribute| vk::VertexInputAttributeDescription {
+                location: attribute.location,
+                binding: binding as u32,
+                format: attribute.format.to_vk(),
+                offset: attribute.offset,
+            })
+        })
+        .collect();
+    (bindings, attributes)
+}
+
+/// Checks that every vertex shader input is fed by exactly one attribute of a matching type
+pub fn check_vertex_inputs(
+    vertex: &ShaderReflection,
+    layouts: &[VertexLayout],
+) -> Result<(), VulkanError> {
+    let attributes: Vec<_> = layouts.iter().flat_map(|layout| layout.attributes.iter()).collect();
+    for (index, attribute) in attributes.iter().enumerate() {
+        if attributes[..index].iter().any(|other| other.location == attribute.location) {
+            return Err(VulkanError::PipelineLayoutMismatch(format!(
+                "Vertex location {} is described by more than one attribute",
+                attribute.location
+            )));
+        }
+    }
+
+    for input in vertex.inputs.iter() {
+        let Some(attribute) = attributes.iter().find(|a| a.location == input.location) else {
+            return Err(VulkanError::PipelineLayoutMismatch(format!(
+                "Vertex input `{}` at location {} has no vertex attribute to read from",
+                input.name, input.location
+            )));
+        };
+        if let Some(ty) = input.ty {
+            if ty.kind != attribute.format.scalar_kind() {
+                return Err(VulkanError::PipelineLayoutMismatch(format!(
+                    "Vertex input `{}` at location {} is {} but the attribute format is {:?}",
+                    input.name, input.location, ty, attribute.format
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::{InterfaceVariable, ValueType};
+
+    fn vertex_shader(inputs: &[(u32, &str, ScalarKind)]) -> ShaderReflection {
+        let inputs = inputs
+            .iter()
+            .map(|&(location, name, kind)| InterfaceVariable {
+                location,
+                name: name.to_string(),
+                ty: Some(ValueType { kind, width: 32, components: 3 }),
+            })
+            .collect();
+        ShaderReflection {
+            stage: vk::ShaderStageFlags::VERTEX,
+            inputs,
+            outputs: vec![],
+            bindings: vec![],
+            push_constant_size: 0,
+        }
+    }
+
+    fn layout(attributes: &[(u32, VertexFormat)]) -> VertexLayout {
+        let attributes = attributes
+            .iter()
+            .map(|&(location, format)| VertexAttribute { location, format, offset: 0 })
+            .collect();
+        VertexLayout { stride: 12, rate: VertexRate::Vertex, attributes }
+    }
+
+    fn message(result: Result<(), VulkanError>) -> String {
+        match result {
+            Err(VulkanError::PipelineLayoutMismatch(message)) => message,
+            other => panic!("Expected a layout mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_inputs_fed_by_attributes() {
+        let vertex =
+            vertex_shader(&[(0, "position", ScalarKind::Float), (1, "id", ScalarKind::Uint)]);
+        let layouts = [
+            layout(&[(0, VertexFormat::Float3)]),
+            layout(&[(1, VertexFormat::Uint3), (2, VertexFormat::Unorm8x4)]),
+        ];
+        assert!(check_vertex_inputs(&vertex, &layouts).is_ok());
+    }
+
+    #[test]
+    fn rejects_duplicate_locations() {
+        let vertex = vertex_shader(&[(0, "position", ScalarKind::Float)]);
+        let layouts = [layout(&[(0, VertexFormat::Float3)]), layout(&[(0, VertexFormat::Float3)])];
+        assert_eq!(
+            message(check_vertex_inputs(&vertex, &layouts)),
+            "Vertex location 0 is described by more than one attribute"
+        );
+    }
+
+    #[test]
+    fn rejects_inputs_without_attribute() {
+        let vertex =
+            vertex_shader(&[(0, "position", ScalarKind::Float), (1, "normal", ScalarKind::Float)]);
+        let layouts = [layout(&[(0, VertexFormat::Float3)])];
+        assert_eq!(
+            message(check_vertex_inputs(&vertex, &layouts)),
+            "Vertex input `normal` at location 1 has no vertex attribute to read from"
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_scalar_kind() {
+        let vertex = vertex_shader(&[(0, "index", ScalarKind::Uint)]);
+        let layouts = [layout(&[(0, VertexFormat::Float3)])];
+        assert_eq!(
+            message(check_vertex_inputs(&vertex, &layouts)),
+            "Vertex input `index` at location 0 is uvec3 but the attribute format is Float3"
+        );
+    }
+}
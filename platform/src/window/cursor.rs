@@ -0,0 +1,155 @@
+use std::rc::Rc;
+
+use windows_sys::Win32::{
+    Graphics::Gdi::{
+        CreateBitmap, CreateDIBSection, DeleteObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+        DIB_RGB_COLORS,
+    },
+    UI::WindowsAndMessaging::*,
+};
+
+use super::{refresh_cursor, Window};
+use crate::utils;
+
+/// Standard cursor shapes of the system
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CursorIcon {
+    #[default]
+    Arrow,
+    /// Pointing hand over links and buttons
+    Hand,
+    /// I-beam over editable text
+    Text,
+    Crosshair,
+    /// Left and right arrows
+    ResizeHorizontal,
+    /// Up and down arrows
+    ResizeVertical,
+    /// Top left to bottom right arrows
+    ResizeNwSe,
+    /// Top right to bottom left arrows
+    ResizeNeSw,
+    /// Four arrows, for moving things around
+    Move,
+    NotAllowed,
+    Wait,
+}
+
+/// Cursor made from an image by `create_custom_cursor`. Clones share the same system cursor,
+/// which is destroyed with the last clone
+#[derive(Debug, Clone)]
+pub struct CustomCursor {
+    handle: Rc<CursorHandle>,
+}
+
+#[derive(Debug)]
+struct CursorHandle(HCURSOR);
+
+impl Drop for CursorHandle {
+    fn drop(&mut self) {
+        unsafe { DestroyIcon(self.0) };
+    }
+}
+
+// PUBLIC FUNCTIONS =============================================================================
+/// Shows `icon` over the client area, replacing any custom cursor
+pub fn set_cursor_icon(window: &mut Window, icon: CursorIcon) {
+    window.internal.cursor_icon = icon;
+    window.internal.custom_cursor = None;
+    if window.internal.initialized {
+        unsafe { refresh_cursor() };
+    }
+}
+
+/// Shows `cursor` over the client area, the window keeps it alive while it's in use
+pub fn set_custom_cursor(window: &mut Window, cursor: &CustomCursor) {
+    window.internal.custom_cursor = Some(cursor.clone());
+    if window.internal.initialized {
+        unsafe { refresh_cursor() };
+    }
+}
+
+/// Makes a cursor from `width` * `height` RGBA pixels, row by row from the top. The hotspot
+/// is the pixel that points. `None` if the pixels don't match the size
+pub fn create_custom_cursor(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    hotspot_x: u32,
+    hotspot_y: u32,
+) -> Option<CustomCursor> {
+    let expected_len = (width as usize).checked_mul(height as usize).and_then(|n| n.checked_mul(4));
+    if width == 0 || height == 0 || expected_len != Some(rgba.len()) {
+        utils::error(format!(
+            "Cursor needs {}x{} RGBA pixels, got {} bytes",
+            width,
+            height,
+            rgba.len()
+        ));
+        return None;
+    }
+
+    unsafe {
+        let mut info: BITMAPINFO = std::mem::zeroed();
+        info.bmiHeader = BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width as i32,
+            // Negative height makes the rows go from the top
+            biHeight: -(height as i32),
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB,
+            ..std::mem::zeroed()
+        };
+        let mut bits = std::ptr::null_mut();
+        let color = CreateDIBSection(0, &info, DIB_RGB_COLORS, &mut bits, 0, 0);
+        if color == 0 || bits.is_null() {
+            utils::error("Cant create cursor bitmap");
+            return None;
+        }
+        let bgra = std::slice::from_raw_parts_mut(bits as *mut u8, rgba.len());
+        for (dst, src) in bgra.chunks_exact_mut(4).zip(rgba.chunks_exact(4)) {
+            dst.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+        }
+        // Unused with an alpha channel, but the cursor needs one
+        let mask = CreateBitmap(width as i32, height as i32, 1, 1, std::ptr::null());
+
+        let icon_info = ICONINFO {
+            fIcon: 0,
+            xHotspot: hotspot_x.min(width - 1),
+            yHotspot: hotspot_y.min(height - 1),
+            hbmMask: mask,
+            hbmColor: color,
+        };
+        let cursor = CreateIconIndirect(&icon_info);
+        DeleteObject(color);
+        DeleteObject(mask);
+        if cursor == 0 {
+            utils::error("Cant create cursor");
+            return None;
+        }
+        Some(CustomCursor { handle: Rc::new(CursorHandle(cursor)) })
+    }
+}
+
+// PRIVATE FUNCTIONS ===========================================================================
+/// Cursor to show over the client area
+pub(super) unsafe fn current_cursor(icon: CursorIcon, custom: Option<&CustomCursor>) -> HCURSOR {
+    if let Some(custom) = custom {
+        return custom.handle.0;
+    }
+    let name = match icon {
+        CursorIcon::Arrow => IDC_ARROW,
+        CursorIcon::Hand => IDC_HAND,
+        CursorIcon::Text => IDC_IBEAM,
+        CursorIcon::Crosshair => IDC_CROSS,
+        CursorIcon::ResizeHorizontal => IDC_SIZEWE,
+        CursorIcon::ResizeVertical => IDC_SIZENS,
+        CursorIcon::ResizeNwSe => IDC_SIZENWSE,
+        CursorIcon::ResizeNeSw => IDC_SIZENESW,
+        CursorIcon::Move => IDC_SIZEALL,
+        CursorIcon::NotAllowed => IDC_NO,
+        CursorIcon::Wait => IDC_WAIT,
+    };
+    LoadCursorW(0, name)
+}
@@ -0,0 +1,224 @@
+use std::collections::HashSet;
+
+use super::{KeyCode, MouseButton, MouseEvent, WheelDelta, WindowEvent};
+
+/// Anything `Input` tracks as held, keys and mouse buttons convert into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputButton {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl From<KeyCode> for InputButton {
+    fn from(key: KeyCode) -> Self {
+        InputButton::Key(key)
+    }
+}
+
+impl From<MouseButton> for InputButton {
+    fn from(button: MouseButton) -> Self {
+        InputButton::Mouse(button)
+    }
+}
+
+/// Input state polled instead of scanning `Window::events`, updated by `update_window`.
+/// "This frame" means since the previous `update_window`
+#[derive(Debug, Default)]
+pub struct Input {
+    down: HashSet<InputButton>,
+    pressed: HashSet<InputButton>,
+    released: HashSet<InputButton>,
+    cursor: Option<(i32, i32)>,
+    cursor_delta: (i32, i32),
+    raw_motion: (i32, i32),
+    wheel: WheelDelta,
+}
+
+impl Input {
+    pub fn is_down(&self, button: impl Into<InputButton>) -> bool {
+        self.down.contains(&button.into())
+    }
+
+    /// Set for the first press only, not for auto-repeat
+    pub fn was_pressed_this_frame(&self, button: impl Into<InputButton>) -> bool {
+        self.pressed.contains(&button.into())
+    }
+
+    pub fn was_released_this_frame(&self, button: impl Into<InputButton>) -> bool {
+        self.released.contains(&button.into())
+    }
+
+    /// Client coordinates of the cursor, `(0, 0)` before it first moved over the window
+    pub fn cursor_position(&self) -> (i32, i32) {
+        self.cursor.unwrap_or_default()
+    }
+
+    pub fn cursor_delta(&self) -> (i32, i32) {
+        self.cursor_delta
+    }
+
+    /// Sum of the `MouseEvent::RawMotion` of this frame, for camera control
+    pub fn raw_motion(&self) -> (i32, i32) {
+        self.raw_motion
+    }
+
+    /// Sum of the wheel events of this frame
+    pub fn wheel(&self) -> WheelDelta {
+        self.wheel
+    }
+
+    /// Starts a new frame from `events`. Losing focus releases everything held at that
+    /// point, the window would not see the releases otherwise
+    pub(crate) fn update(&mut self, events: &[WindowEvent]) {
+        self.pressed.clear();
+        self.released.clear();
+        self.cursor_delta = (0, 0);
+        self.raw_motion = (0, 0);
+        self.wheel = WheelDelta::default();
+
+        for event in events {
+            match event {
+                WindowEvent::Key { pressed, key, repeat: false, .. } => {
+                    self.set_button(InputButton::Key(*key), *pressed)
+                }
+                WindowEvent::Mouse { event, .. } => match event {
+                    MouseEvent::Button { pressed, button } => {
+                        self.set_button(InputButton::Mouse(*button), *pressed)
+                    }
+                    MouseEvent::Move { x, y } => {
+                        if let Some((last_x, last_y)) = self.cursor {
+                            self.cursor_delta.0 += x - last_x;
+                            self.cursor_delta.1 += y - last_y;
+                        }
+                        self.cursor = Some((*x, *y));
+                    }
+                    MouseEvent::RawMotion { dx, dy } => {
+                        self.raw_motion.0 += dx;
+                        self.raw_motion.1 += dy;
+                    }
+                    MouseEvent::Wheel { delta } => {
+                        self.wheel.x += delta.x;
+                        self.wheel.y += delta.y;
+                    }
+                    _ => {}
+                },
+                WindowEvent::Focused(false) => self.released.extend(self.down.drain()),
+                _ => {}
+            }
+        }
+    }
+
+    fn set_button(&mut self, button: InputButton, pressed: bool) {
+        if pressed {
+            if self.down.insert(button) {
+                self.pressed.insert(button);
+            }
+        } else if self.down.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window::{KeyCode, LogicalKey, Modifiers};
+
+    fn key(key: KeyCode, pressed: bool, repeat: bool) -> WindowEvent {
+        WindowEvent::Key {
+            pressed,
+            key,
+            logical: LogicalKey::Named(key),
+            repeat,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    fn mouse(event: MouseEvent) -> WindowEvent {
+        WindowEvent::Mouse { event, modifiers: Modifiers::default() }
+    }
+
+    #[test]
+    fn press_and_release_in_one_frame() {
+        let mut input = Input::default();
+        input.update(&[key(KeyCode::Space, true, false), key(KeyCode::Space, false, false)]);
+        assert!(input.was_pressed_this_frame(KeyCode::Space));
+        assert!(input.was_released_this_frame(KeyCode::Space));
+        assert!(!input.is_down(KeyCode::Space));
+
+        input.update(&[]);
+        assert!(!input.was_pressed_this_frame(KeyCode::Space));
+        assert!(!input.was_released_this_frame(KeyCode::Space));
+    }
+
+    #[test]
+    fn ignores_repeats() {
+        let mut input = Input::default();
+        input.update(&[key(KeyCode::KeyW, true, false)]);
+        input.update(&[key(KeyCode::KeyW, true, true), key(KeyCode::KeyW, true, true)]);
+        assert!(input.is_down(KeyCode::KeyW));
+        assert!(!input.was_pressed_this_frame(KeyCode::KeyW));
+
+        // A repeat never starts a press on its own
+        input.update(&[key(KeyCode::KeyA, true, true)]);
+        assert!(!input.is_down(KeyCode::KeyA));
+    }
+
+    #[test]
+    fn focus_loss_releases_held_buttons() {
+        let mut input = Input::default();
+        input.update(&[
+            key(KeyCode::KeyW, true, false),
+            mouse(MouseEvent::Button { pressed: true, button: MouseButton::Left }),
+        ]);
+        input.update(&[WindowEvent::Focused(false)]);
+        assert!(!input.is_down(KeyCode::KeyW));
+        assert!(!input.is_down(MouseButton::Left));
+        assert!(input.was_released_this_frame(KeyCode::KeyW));
+        assert!(input.was_released_this_frame(MouseButton::Left));
+    }
+
+    #[test]
+    fn press_after_focus_regained_stays_down() {
+        let mut input = Input::default();
+        input.update(&[
+            WindowEvent::Focused(false),
+            WindowEvent::Focused(true),
+            key(KeyCode::KeyW, true, false),
+        ]);
+        assert!(input.is_down(KeyCode::KeyW));
+        assert!(input.was_pressed_this_frame(KeyCode::KeyW));
+        assert!(!input.was_released_this_frame(KeyCode::KeyW));
+    }
+
+    #[test]
+    fn cursor_delta_starts_at_first_move() {
+        let mut input = Input::default();
+        input.update(&[mouse(MouseEvent::Move { x: 10, y: 20 })]);
+        assert_eq!(input.cursor_position(), (10, 20));
+        assert_eq!(input.cursor_delta(), (0, 0));
+
+        input.update(&[
+            mouse(MouseEvent::Move { x: 15, y: 18 }),
+            mouse(MouseEvent::Move { x: 12, y: 30 }),
+        ]);
+        assert_eq!(input.cursor_position(), (12, 30));
+        assert_eq!(input.cursor_delta(), (2, 10));
+
+        input.update(&[]);
+        assert_eq!(input.cursor_delta(), (0, 0));
+    }
+
+    #[test]
+    fn wheel_accumulates_per_frame() {
+        let mut input = Input::default();
+        input.update(&[
+            mouse(MouseEvent::Wheel { delta: WheelDelta { x: 0, y: 120 } }),
+            mouse(MouseEvent::Wheel { delta: WheelDelta { x: -30, y: 120 } }),
+        ]);
+        assert_eq!(input.wheel(), WheelDelta { x: -30, y: 240 });
+
+        input.update(&[]);
+        assert_eq!(input.wheel(), WheelDelta::default());
+    }
+}
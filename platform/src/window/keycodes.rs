@@ -1,4 +1,9 @@
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+use windows_sys::Win32::UI::{
+    Input::KeyboardAndMouse::{GetKeyNameTextW, ToUnicodeEx, VIRTUAL_KEY},
+    TextServices::HKL,
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum KeyCode {
     Unidentified,
     Backquote,
@@ -148,6 +153,18 @@ pub enum KeyCode {
     F35,
 }
 
+/// Key as labeled under the current keyboard layout, ignoring modifiers. Use it for
+/// shortcuts like Ctrl+Z, use `KeyCode` for layout independent bindings like WASD
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum LogicalKey {
+    /// Lowercase character the key types, e.g. `'й'` for `KeyCode::KeyQ` on a Russian layout
+    Character(char),
+    /// Dead key of an accent, which combines with the next character
+    Dead(char),
+    /// Keys that type no character, e.g. arrows, `Enter` and function keys
+    Named(KeyCode),
+}
+
 pub fn scancode_to_key(scancode: u32) -> KeyCode {
     match scancode {
         0x0029 => KeyCode::Backquote,
@@ -276,3 +293,56 @@ pub fn scancode_to_key(scancode: u32) -> KeyCode {
         _ => return KeyCode::Unidentified,
     }
 }
+
+pub fn key_to_scancode(key: KeyCode) -> Option<u32> {
+    // Both have a second scancode when pressed with a modifier
+    match key {
+        KeyCode::PrintScreen => Some(0xe037),
+        KeyCode::Pause => Some(0x0045),
+        _ => (0x0000..0x0080)
+            .chain(0xe000..0xe080)
+            .find(|scancode| scancode_to_key(*scancode) == key),
+    }
+}
+
+pub fn logical_key(v_key: VIRTUAL_KEY, scancode: u32, key: KeyCode, layout: HKL) -> LogicalKey {
+    let key_state = [0u8; 256];
+    let mut buffer = [0u16; 8];
+    // Flag 0x4 leaves the dead key state of the keyboard alone, so WM_CHAR still combines
+    let length = unsafe {
+        ToUnicodeEx(
+            v_key as u32,
+            scancode,
+            key_state.as_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len() as i32,
+            0x4,
+            layout,
+        )
+    };
+    let character = char::decode_utf16(buffer[..length.unsigned_abs() as usize].iter().copied())
+        .next()
+        .and_then(Result::ok)
+        .filter(|c| !c.is_control());
+    match character {
+        Some(c) if length < 0 => LogicalKey::Dead(c),
+        Some(c) => LogicalKey::Character(c.to_lowercase().next().unwrap_or(c)),
+        None => LogicalKey::Named(key),
+    }
+}
+
+/// Name of the key under the current keyboard layout, in the language of the layout,
+/// for showing key bindings to players
+pub fn key_name(key: KeyCode) -> String {
+    let Some(scancode) = key_to_scancode(key) else {
+        return format!("{:?}", key);
+    };
+    let extended = if scancode & 0xe000 != 0 { 1 << 24 } else { 0 };
+    let lparam = ((scancode & 0xff) << 16 | extended) as i32;
+    let mut buffer = [0u16; 64];
+    let length = unsafe { GetKeyNameTextW(lparam, buffer.as_mut_ptr(), buffer.len() as i32) };
+    if length <= 0 {
+        return format!("{:?}", key);
+    }
+    String::from_utf16_lossy(&buffer[..length as usize])
+}
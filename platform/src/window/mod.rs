@@ -2,25 +2,59 @@ use crate::utils;
 use std::time::{Duration, Instant};
 use windows_sys::Win32::{
     Foundation::*,
+    Globalization::HIMC,
+    Graphics::Gdi::ClientToScreen,
     System::LibraryLoader::GetModuleHandleW,
     UI::{
-        Input::KeyboardAndMouse::{
-            GetKeyboardLayout, MapVirtualKeyExW, MAPVK_VK_TO_VSC_EX, VIRTUAL_KEY,
+        Controls::WM_MOUSELEAVE,
+        Input::{
+            GetRawInputData,
+            Ime::{
+                ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext, ImmSetCandidateWindow,
+                ImmSetCompositionWindow, CANDIDATEFORM, CFS_CANDIDATEPOS, CFS_POINT,
+                COMPOSITIONFORM, GCS_COMPSTR, GCS_CURSORPOS, GCS_RESULTSTR, IME_COMPOSITION_STRING,
+            },
+            KeyboardAndMouse::{
+                GetFocus, GetKeyState, GetKeyboardLayout, MapVirtualKeyExW, ReleaseCapture,
+                SetCapture, TrackMouseEvent, MAPVK_VK_TO_VSC_EX, TME_LEAVE, TRACKMOUSEEVENT,
+                VIRTUAL_KEY, VK_CAPITAL, VK_CONTROL, VK_F4, VK_LWIN, VK_MENU, VK_NUMLOCK, VK_RWIN,
+                VK_SHIFT,
+            },
+            RegisterRawInputDevices, HRAWINPUT, RAWINPUT, RAWINPUTDEVICE, RAWINPUTHEADER,
+            RID_INPUT, RIM_TYPEMOUSE,
         },
         WindowsAndMessaging::*,
     },
 };
 
-use keycodes::scancode_to_key;
-pub use keycodes::KeyCode;
+pub use keycodes::{key_name, KeyCode, LogicalKey};
+use keycodes::{logical_key, scancode_to_key};
 mod keycodes;
 
+pub use input::{Input, InputButton};
+mod input;
+
+use cursor::current_cursor;
+pub use cursor::{
+    create_custom_cursor, set_cursor_icon, set_custom_cursor, CursorIcon, CustomCursor,
+};
+mod cursor;
+
+/// `RAWMOUSE::usFlags` of devices like tablets that report positions instead of motion
+const MOUSE_MOVE_ABSOLUTE: u16 = 0x1;
+
 pub struct Window {
     pub title: String,
     pub outer_size: PhysicalSize,
     pub inner_size: PhysicalSize,
     pub events: Vec<WindowEvent>,
+    pub input: Input,
     pub exists: bool,
+    pub focused: bool,
+    pub minimized: bool,
+    pub maximized: bool,
+    /// Nothing of the window can be seen, because it's minimized or hidden
+    pub occluded: bool,
     pub(crate) internal: Box<WindowInternal>,
 }
 
@@ -48,7 +82,12 @@ impl Window {
             outer_size: PhysicalSize::new(width, height),
             inner_size: PhysicalSize::new(width, height),
             events: vec![],
+            input: Input::default(),
             exists: true,
+            focused: false,
+            minimized: false,
+            maximized: false,
+            occluded: false,
             internal: Default::default(),
         }
     }
@@ -70,28 +109,151 @@ pub(crate) struct WindowInternal {
     pub hinstance: HINSTANCE,
     pub hwnd: HWND,
     pub events: Vec<WindowEvent>,
+    /// First half of a surrogate pair waiting for its `WM_CHAR`
+    pub high_surrogate: Option<u16>,
+    /// Mouse buttons down while the window has the mouse captured
+    pub buttons_held: u32,
+    /// `TrackMouseEvent` is waiting to send `WM_MOUSELEAVE`
+    pub tracking_mouse: bool,
+    pub cursor_grab: CursorGrab,
+    pub cursor_hidden: bool,
+    pub cursor_icon: CursorIcon,
+    /// Shown instead of `cursor_icon` when set
+    pub custom_cursor: Option<CustomCursor>,
+    pub focused: bool,
+    pub minimized: bool,
+    pub maximized: bool,
+    /// Hidden by `WM_SHOWWINDOW`
+    pub hidden: bool,
+    pub occluded: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum WindowEvent {
-    Mouse { event: MouseEvent },
-    Key { pressed: bool, key: KeyCode },
+    Mouse {
+        event: MouseEvent,
+        modifiers: Modifiers,
+    },
+    /// `key` is the physical key, `logical` what it means under the current layout.
+    /// `repeat` is set for auto-repeated presses while the key is held
+    Key {
+        pressed: bool,
+        key: KeyCode,
+        logical: LogicalKey,
+        repeat: bool,
+        modifiers: Modifiers,
+    },
+    /// Typed characters, without control characters. Text entered through an IME
+    /// arrives as `ImeEvent::Commit` instead
+    Text {
+        text: String,
+    },
+    Ime {
+        event: ImeEvent,
+    },
     Resize,
+    /// The window gained or lost keyboard focus
+    Focused(bool),
+    Minimized,
+    Maximized,
+    /// The window left the minimized or maximized state
+    Restored,
+    /// The window stopped or started being visible. Win32 reports minimized and hidden
+    /// windows, not ones covered by other windows
+    Occluded(bool),
     Close,
 }
 
+/// How the cursor is kept in the window while it has focus, see `set_cursor_grab`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CursorGrab {
+    #[default]
+    None,
+    /// The cursor can't leave the client area
+    Confined,
+    /// The cursor stays at the center of the client area, read `MouseEvent::RawMotion`
+    Locked,
+}
+
+/// Modifier keys held, and lock keys toggled on, when the event happened
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// Windows key
+    pub super_key: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+}
+
+/// Input method composition, e.g. for Chinese or Japanese. The game draws the preedit text
+/// itself, `set_ime_position` places the candidate list next to it
 #[derive(Debug, PartialEq, Eq)]
-pub enum MouseEvent {
-    Move { x: i32, y: i32 },
-    Button { pressed: bool, button: MouseButton },
-    Wheel,
+pub enum ImeEvent {
+    /// Text being composed, empty when the composition ends. `cursor` is a byte offset
+    Preedit { text: String, cursor: Option<usize> },
+    /// Finished text to insert
+    Commit { text: String },
 }
 
 #[derive(Debug, PartialEq, Eq)]
+pub enum MouseEvent {
+    /// Physical pixels relative to the top left of the client area, y pointing down. While a
+    /// button is held the window keeps the mouse, so drags can go negative or past `inner_size`
+    Move {
+        x: i32,
+        y: i32,
+    },
+    Button {
+        pressed: bool,
+        button: MouseButton,
+    },
+    Wheel {
+        delta: WheelDelta,
+    },
+    /// Unaccelerated motion from the mouse itself, also sent while the cursor is locked
+    RawMotion {
+        dx: i32,
+        dy: i32,
+    },
+    /// Follows the `Button` press of the second click
+    DoubleClick {
+        button: MouseButton,
+    },
+    /// The cursor moved over the client area
+    CursorEnter,
+    /// The cursor left the client area
+    CursorLeave,
+}
+
+/// Scroll amount in `WHEEL_DELTA` units (120 per notch), touchpads send fractions of a notch.
+/// Positive `y` scrolls up, away from the user, positive `x` scrolls right
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WheelDelta {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl WheelDelta {
+    pub fn notches_x(self) -> f32 {
+        self.x as f32 / WHEEL_DELTA as f32
+    }
+
+    pub fn notches_y(self) -> f32 {
+        self.y as f32 / WHEEL_DELTA as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MouseButton {
     Left,
     Right,
     Middle,
+    /// Back side button
+    X1,
+    /// Forward side button
+    X2,
 }
 
 // PUBLIC FUNCTIONS =============================================================================
@@ -102,14 +264,60 @@ pub fn update_window(window: &mut Window) {
     }
     get_events_with_timeout(window.internal.as_mut(), 10);
     window.events = window.internal.events.drain(..).collect();
+    window.input.update(&window.events);
     window.inner_size = window.internal.inner_size;
     window.outer_size = window.internal.outer_size;
+    window.focused = window.internal.focused;
+    window.minimized = window.internal.minimized;
+    window.maximized = window.internal.maximized;
+    window.occluded = window.internal.occluded;
 
     if window.internal.destroyed {
         window.exists = false;
     }
 }
 
+/// Confines or locks the cursor while the window has focus, typically with a hidden cursor
+/// for camera control
+pub fn set_cursor_grab(window: &mut Window, grab: CursorGrab) {
+    window.internal.cursor_grab = grab;
+    if window.internal.initialized {
+        unsafe { apply_cursor_grab(&window.internal) };
+    }
+}
+
+/// Hides or shows the cursor over the client area
+pub fn set_cursor_visible(window: &mut Window, visible: bool) {
+    window.internal.cursor_hidden = !visible;
+    if window.internal.initialized {
+        unsafe { refresh_cursor() };
+    }
+}
+
+/// Moves the IME composition and candidate windows to `x`, `y` in client coordinates,
+/// usually the text cursor
+pub fn set_ime_position(window: &Window, x: i32, y: i32) {
+    unsafe {
+        let himc = ImmGetContext(window.internal.hwnd);
+        if himc == 0 {
+            return;
+        }
+        let position = POINT { x, y };
+        let area = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+        let composition =
+            COMPOSITIONFORM { dwStyle: CFS_POINT, ptCurrentPos: position, rcArea: area };
+        ImmSetCompositionWindow(himc, &composition);
+        let candidate = CANDIDATEFORM {
+            dwIndex: 0,
+            dwStyle: CFS_CANDIDATEPOS,
+            ptCurrentPos: position,
+            rcArea: area,
+        };
+        ImmSetCandidateWindow(himc, &candidate);
+        ImmReleaseContext(window.internal.hwnd, himc);
+    }
+}
+
 // PRIVATE FUNCTIONS ===========================================================================
 fn create_window(name: &str, size: PhysicalSize, internal: &mut WindowInternal) {
     unsafe {
@@ -135,6 +343,16 @@ fn create_window(name: &str, size: PhysicalSize, internal: &mut WindowInternal)
             std::ptr::from_mut(internal) as *const std::ffi::c_void,
         );
         internal.initialized = true;
+
+        let device = RAWINPUTDEVICE {
+            usUsagePage: 0x01, // Generic desktop
+            usUsage: 0x02,     // Mouse
+            dwFlags: 0,
+            hwndTarget: internal.hwnd,
+        };
+        if RegisterRawInputDevices(&device, 1, std::mem::size_of::<RAWINPUTDEVICE>() as u32) == 0 {
+            utils::error("Cant register raw mouse input");
+        }
     }
     utils::trace(format!("Window {:?} created", name));
 }
@@ -170,61 +388,201 @@ extern "system" fn wndproc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPA
         if let Some(internal) = internal_ptr.as_mut() {
             match message {
                 WM_SIZE => {
-                    let size = PhysicalSize::from_lparam(lparam);
-                    let mut rect: RECT = RECT { left: 0, top: 0, right: 0, bottom: 0 };
-                    let result = GetWindowRect(hwnd, &mut rect);
-                    assert_eq!(result, 1);
-                    let outer_size = PhysicalSize::new(
-                        (rect.right - rect.left) as u32,
-                        (rect.bottom - rect.top) as u32,
-                    );
+                    match wparam as u32 {
+                        // Sizes stay as they were, the client area is empty while minimized
+                        SIZE_MINIMIZED if !internal.minimized => {
+                            internal.minimized = true;
+                            internal.events.push(WindowEvent::Minimized);
+                        }
+                        SIZE_RESTORED | SIZE_MAXIMIZED => {
+                            let maximized = wparam as u32 == SIZE_MAXIMIZED;
+                            if maximized && (!internal.maximized || internal.minimized) {
+                                internal.events.push(WindowEvent::Maximized);
+                            } else if !maximized && (internal.maximized || internal.minimized) {
+                                internal.events.push(WindowEvent::Restored);
+                            }
+                            internal.minimized = false;
+                            internal.maximized = maximized;
+
+                            let size = PhysicalSize::from_lparam(lparam);
+                            let mut rect: RECT = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+                            let result = GetWindowRect(hwnd, &mut rect);
+                            assert_eq!(result, 1);
+                            let outer_size = PhysicalSize::new(
+                                (rect.right - rect.left) as u32,
+                                (rect.bottom - rect.top) as u32,
+                            );
 
-                    internal.inner_size = size;
-                    internal.outer_size = outer_size;
-                    internal.events.push(WindowEvent::Resize);
+                            internal.inner_size = size;
+                            internal.outer_size = outer_size;
+                            internal.events.push(WindowEvent::Resize);
+                            apply_cursor_grab(internal);
+                        }
+                        _ => {}
+                    }
+                    update_occluded(internal);
                 }
-                WM_KEYDOWN => {
-                    let v_key = wparam as VIRTUAL_KEY;
-                    let scancode = MapVirtualKeyExW(v_key as u32, MAPVK_VK_TO_VSC_EX, kb_layout);
-                    internal
-                        .events
-                        .push(WindowEvent::Key { pressed: true, key: scancode_to_key(scancode) });
+                WM_SHOWWINDOW => {
+                    internal.hidden = wparam == 0;
+                    update_occluded(internal);
+                }
+                WM_MOVE => {
+                    apply_cursor_grab(internal);
                 }
-                WM_KEYUP => {
+                WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP => {
                     let v_key = wparam as VIRTUAL_KEY;
                     let scancode = MapVirtualKeyExW(v_key as u32, MAPVK_VK_TO_VSC_EX, kb_layout);
-                    internal
-                        .events
-                        .push(WindowEvent::Key { pressed: false, key: scancode_to_key(scancode) });
-                }
-                WM_LBUTTONDOWN => {
-                    internal.events.push(WindowEvent::Mouse {
-                        event: MouseEvent::Button { pressed: true, button: MouseButton::Left },
+                    let pressed = message == WM_KEYDOWN || message == WM_SYSKEYDOWN;
+                    // Bit 30 is the previous key state, set when the press is a repeat
+                    let repeat = pressed && (lparam >> 30) & 1 != 0;
+                    let key = scancode_to_key(scancode);
+                    internal.events.push(WindowEvent::Key {
+                        pressed,
+                        key,
+                        logical: logical_key(v_key, scancode, key, kb_layout),
+                        repeat,
+                        modifiers: current_modifiers(),
                     });
+                    // Keeps Alt and F10 from opening the window menu, Alt+F4 still closes
+                    if (message == WM_SYSKEYDOWN || message == WM_SYSKEYUP) && v_key != VK_F4 {
+                        result = 0;
+                    }
                 }
-                WM_LBUTTONUP => {
-                    internal.events.push(WindowEvent::Mouse {
-                        event: MouseEvent::Button { pressed: false, button: MouseButton::Left },
-                    });
+                WM_SYSCHAR => {
+                    // Alt+letter would beep looking for a menu accelerator
+                    result = 0;
                 }
-                WM_RBUTTONDOWN => {
-                    internal.events.push(WindowEvent::Mouse {
-                        event: MouseEvent::Button { pressed: true, button: MouseButton::Right },
+                WM_CHAR => {
+                    let unit = wparam as u16;
+                    if (0xd800..0xdc00).contains(&unit) {
+                        internal.high_surrogate = Some(unit);
+                    } else {
+                        let units = internal.high_surrogate.take().into_iter().chain([unit]);
+                        let text: String = char::decode_utf16(units)
+                            .filter_map(Result::ok)
+                            .filter(|c| !c.is_control())
+                            .collect();
+                        if !text.is_empty() {
+                            internal.events.push(WindowEvent::Text { text });
+                        }
+                    }
+                    result = 0;
+                }
+                WM_IME_COMPOSITION => {
+                    let himc = ImmGetContext(hwnd);
+                    if himc != 0 {
+                        let flags = lparam as IME_COMPOSITION_STRING;
+                        if flags & GCS_RESULTSTR != 0 {
+                            let text = composition_string(himc, GCS_RESULTSTR);
+                            internal
+                                .events
+                                .push(WindowEvent::Ime { event: ImeEvent::Commit { text } });
+                        }
+                        if flags & GCS_COMPSTR != 0 {
+                            let text = composition_string(himc, GCS_COMPSTR);
+                            let cursor = (flags & GCS_CURSORPOS != 0).then(|| {
+                                let position = ImmGetCompositionStringW(
+                                    himc,
+                                    GCS_CURSORPOS,
+                                    std::ptr::null_mut(),
+                                    0,
+                                );
+                                utf16_to_byte_offset(&text, position.max(0) as usize)
+                            });
+                            internal.events.push(WindowEvent::Ime {
+                                event: ImeEvent::Preedit { text, cursor },
+                            });
+                        }
+                        ImmReleaseContext(hwnd, himc);
+                    }
+                    // DefWindowProc would send the result again as WM_CHAR
+                    result = 0;
+                }
+                WM_IME_ENDCOMPOSITION => {
+                    internal.events.push(WindowEvent::Ime {
+                        event: ImeEvent::Preedit { text: String::new(), cursor: None },
                     });
                 }
-                WM_RBUTTONUP => {
+                WM_LBUTTONDOWN | WM_LBUTTONUP | WM_LBUTTONDBLCLK | WM_RBUTTONDOWN
+                | WM_RBUTTONUP | WM_RBUTTONDBLCLK | WM_MBUTTONDOWN | WM_MBUTTONUP
+                | WM_MBUTTONDBLCLK | WM_XBUTTONDOWN | WM_XBUTTONUP | WM_XBUTTONDBLCLK => {
+                    let (button, pressed, double_click) = mouse_button_message(message, wparam);
+                    // Capture keeps drags reporting moves and releases outside the window
+                    if pressed {
+                        if internal.buttons_held == 0 {
+                            SetCapture(hwnd);
+                        }
+                        internal.buttons_held += 1;
+                    } else if internal.buttons_held > 0 {
+                        internal.buttons_held -= 1;
+                        if internal.buttons_held == 0 {
+                            ReleaseCapture();
+                        }
+                    }
+                    let modifiers = current_modifiers();
                     internal.events.push(WindowEvent::Mouse {
-                        event: MouseEvent::Button { pressed: false, button: MouseButton::Right },
+                        event: MouseEvent::Button { pressed, button },
+                        modifiers,
                     });
+                    if double_click {
+                        internal.events.push(WindowEvent::Mouse {
+                            event: MouseEvent::DoubleClick { button },
+                            modifiers,
+                        });
+                    }
+                    // X buttons are the only ones that must return TRUE
+                    result = match button {
+                        MouseButton::X1 | MouseButton::X2 => 1,
+                        _ => 0,
+                    };
                 }
                 WM_MOUSEMOVE => {
+                    if !internal.tracking_mouse {
+                        let mut track = TRACKMOUSEEVENT {
+                            cbSize: std::mem::size_of::<TRACKMOUSEEVENT>() as u32,
+                            dwFlags: TME_LEAVE,
+                            hwndTrack: hwnd,
+                            dwHoverTime: 0,
+                        };
+                        internal.tracking_mouse = TrackMouseEvent(&mut track) != 0;
+                        internal.events.push(WindowEvent::Mouse {
+                            event: MouseEvent::CursorEnter,
+                            modifiers: current_modifiers(),
+                        });
+                    }
                     let x = get_x_lparam(lparam as _);
                     let y = get_y_lparam(lparam as _);
-                    internal.events.push(WindowEvent::Mouse { event: MouseEvent::Move { x, y } });
+                    internal.events.push(WindowEvent::Mouse {
+                        event: MouseEvent::Move { x, y },
+                        modifiers: current_modifiers(),
+                    });
+                    result = 0;
+                }
+                WM_CAPTURECHANGED => {
+                    internal.buttons_held = 0;
+                }
+                WM_MOUSELEAVE => {
+                    internal.tracking_mouse = false;
+                    internal.events.push(WindowEvent::Mouse {
+                        event: MouseEvent::CursorLeave,
+                        modifiers: current_modifiers(),
+                    });
                     result = 0;
                 }
                 WM_MOUSEWHEEL => {
-                    internal.events.push(WindowEvent::Mouse { event: MouseEvent::Wheel });
+                    let delta = WheelDelta { x: 0, y: get_wheel_delta_wparam(wparam) };
+                    internal.events.push(WindowEvent::Mouse {
+                        event: MouseEvent::Wheel { delta },
+                        modifiers: current_modifiers(),
+                    });
+                    result = 0;
+                }
+                WM_MOUSEHWHEEL => {
+                    let delta = WheelDelta { x: get_wheel_delta_wparam(wparam), y: 0 };
+                    internal.events.push(WindowEvent::Mouse {
+                        event: MouseEvent::Wheel { delta },
+                        modifiers: current_modifiers(),
+                    });
                     result = 0;
                 }
                 WM_CLOSE => {
@@ -233,6 +591,48 @@ extern "system" fn wndproc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPA
                     PostQuitMessage(0);
                     result = 0;
                 }
+                WM_SETFOCUS => {
+                    internal.focused = true;
+                    internal.events.push(WindowEvent::Focused(true));
+                    apply_cursor_grab(internal);
+                }
+                WM_KILLFOCUS => {
+                    internal.focused = false;
+                    internal.events.push(WindowEvent::Focused(false));
+                    if internal.cursor_grab != CursorGrab::None {
+                        ClipCursor(std::ptr::null());
+                    }
+                }
+                WM_SETCURSOR if get_loword(lparam as _) == HTCLIENT => {
+                    let cursor = if internal.cursor_hidden {
+                        0
+                    } else {
+                        current_cursor(internal.cursor_icon, internal.custom_cursor.as_ref())
+                    };
+                    SetCursor(cursor);
+                    result = 1;
+                }
+                WM_INPUT => {
+                    let mut raw: RAWINPUT = std::mem::zeroed();
+                    let mut size = std::mem::size_of::<RAWINPUT>() as u32;
+                    let read = GetRawInputData(
+                        lparam as HRAWINPUT,
+                        RID_INPUT,
+                        std::ptr::from_mut(&mut raw) as _,
+                        &mut size,
+                        std::mem::size_of::<RAWINPUTHEADER>() as u32,
+                    );
+                    if read != u32::MAX && raw.header.dwType == RIM_TYPEMOUSE {
+                        let mouse = raw.data.mouse;
+                        let moved = mouse.lLastX != 0 || mouse.lLastY != 0;
+                        if mouse.usFlags & MOUSE_MOVE_ABSOLUTE == 0 && moved {
+                            internal.events.push(WindowEvent::Mouse {
+                                event: MouseEvent::RawMotion { dx: mouse.lLastX, dy: mouse.lLastY },
+                                modifiers: current_modifiers(),
+                            });
+                        }
+                    }
+                }
                 WM_DESTROY => {
                     internal.destroyed = true;
                     PostQuitMessage(0);
@@ -253,13 +653,13 @@ extern "system" fn wndproc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPA
 unsafe fn register_window_class(hinstance: HINSTANCE, class_name: &[u16]) {
     let class = WNDCLASSEXW {
         cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
-        style: CS_HREDRAW | CS_VREDRAW,
+        style: CS_HREDRAW | CS_VREDRAW | CS_DBLCLKS,
         lpfnWndProc: Some(wndproc),
         cbClsExtra: 0,
         cbWndExtra: 0,
         hInstance: hinstance,
         hIcon: 0,
-        hCursor: 0,
+        hCursor: LoadCursorW(0, IDC_ARROW),
         hbrBackground: 0,
         lpszMenuName: std::ptr::null(),
         lpszClassName: class_name.as_ptr(),
@@ -269,6 +669,101 @@ unsafe fn register_window_class(hinstance: HINSTANCE, class_name: &[u16]) {
     debug_assert_ne!(registered, 0);
 }
 
+fn update_occluded(internal: &mut WindowInternal) {
+    let occluded = internal.minimized || internal.hidden;
+    if occluded != internal.occluded {
+        internal.occluded = occluded;
+        internal.events.push(WindowEvent::Occluded(occluded));
+    }
+}
+
+/// Clips the cursor for `WindowInternal::cursor_grab`, only while the window has focus
+unsafe fn apply_cursor_grab(internal: &WindowInternal) {
+    if GetFocus() != internal.hwnd {
+        return;
+    }
+    let mut client = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+    GetClientRect(internal.hwnd, &mut client);
+    let mut top_left = POINT { x: client.left, y: client.top };
+    let mut bottom_right = POINT { x: client.right, y: client.bottom };
+    ClientToScreen(internal.hwnd, &mut top_left);
+    ClientToScreen(internal.hwnd, &mut bottom_right);
+    match internal.cursor_grab {
+        CursorGrab::None => ClipCursor(std::ptr::null()),
+        CursorGrab::Confined => ClipCursor(&RECT {
+            left: top_left.x,
+            top: top_left.y,
+            right: bottom_right.x,
+            bottom: bottom_right.y,
+        }),
+        CursorGrab::Locked => {
+            let x = (top_left.x + bottom_right.x) / 2;
+            let y = (top_left.y + bottom_right.y) / 2;
+            SetCursorPos(x, y);
+            ClipCursor(&RECT { left: x, top: y, right: x + 1, bottom: y + 1 })
+        }
+    };
+}
+
+/// Makes Windows send `WM_SETCURSOR` again, so cursor changes show without moving the mouse
+unsafe fn refresh_cursor() {
+    let mut position = POINT { x: 0, y: 0 };
+    if GetCursorPos(&mut position) != 0 {
+        SetCursorPos(position.x, position.y);
+    }
+}
+
+/// Button, pressed and double click of a `WM_*BUTTON*` message
+fn mouse_button_message(message: u32, wparam: WPARAM) -> (MouseButton, bool, bool) {
+    let button = match message {
+        WM_LBUTTONDOWN | WM_LBUTTONUP | WM_LBUTTONDBLCLK => MouseButton::Left,
+        WM_RBUTTONDOWN | WM_RBUTTONUP | WM_RBUTTONDBLCLK => MouseButton::Right,
+        WM_MBUTTONDOWN | WM_MBUTTONUP | WM_MBUTTONDBLCLK => MouseButton::Middle,
+        _ if get_hiword(wparam as _) as u16 == XBUTTON1 => MouseButton::X1,
+        _ => MouseButton::X2,
+    };
+    let double_click = matches!(
+        message,
+        WM_LBUTTONDBLCLK | WM_RBUTTONDBLCLK | WM_MBUTTONDBLCLK | WM_XBUTTONDBLCLK
+    );
+    let released = matches!(message, WM_LBUTTONUP | WM_RBUTTONUP | WM_MBUTTONUP | WM_XBUTTONUP);
+    (button, !released, double_click)
+}
+
+unsafe fn current_modifiers() -> Modifiers {
+    let down = |key: VIRTUAL_KEY| GetKeyState(key as i32) < 0;
+    let toggled = |key: VIRTUAL_KEY| GetKeyState(key as i32) & 1 != 0;
+    Modifiers {
+        shift: down(VK_SHIFT),
+        ctrl: down(VK_CONTROL),
+        alt: down(VK_MENU),
+        super_key: down(VK_LWIN) || down(VK_RWIN),
+        caps_lock: toggled(VK_CAPITAL),
+        num_lock: toggled(VK_NUMLOCK),
+    }
+}
+
+unsafe fn composition_string(himc: HIMC, kind: IME_COMPOSITION_STRING) -> String {
+    let size = ImmGetCompositionStringW(himc, kind, std::ptr::null_mut(), 0);
+    if size <= 0 {
+        return String::new();
+    }
+    let mut buffer = vec![0u16; size as usize / 2];
+    ImmGetCompositionStringW(himc, kind, buffer.as_mut_ptr() as _, size as u32);
+    String::from_utf16_lossy(&buffer)
+}
+
+fn utf16_to_byte_offset(text: &str, utf16_offset: usize) -> usize {
+    let mut units = 0;
+    for (offset, c) in text.char_indices() {
+        if units >= utf16_offset {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
 fn get_loword(lparam: u32) -> u32 {
     lparam & 0xffff
 }
@@ -277,10 +772,14 @@ fn get_hiword(lparam: u32) -> u32 {
     (lparam >> 16) & 0xffff
 }
 
+fn get_wheel_delta_wparam(wparam: WPARAM) -> i32 {
+    (wparam >> 16) as u16 as i16 as i32
+}
+
 fn get_x_lparam(lparam: i32) -> i32 {
-    lparam & 0xffff
+    lparam as i16 as i32
 }
 
 fn get_y_lparam(lparam: i32) -> i32 {
-    (lparam >> 16) & 0xffff
+    (lparam >> 16) as i16 as i32
 }